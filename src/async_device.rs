@@ -0,0 +1,179 @@
+//! Async variant of [`Device`] for tokio based applications, available with the `tokio` feature. See [`AsyncDevice`]
+
+use crate::{
+    backlight::check_max, err::BlibError, sweep::step_towards, Backlight, BlResult, Delay, Device,
+    Direction, SYSFS,
+};
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+use tokio::{fs, task, time};
+
+/// An async counterpart of [`Device`], which never blocks the runtime it's used on.
+///
+/// Detection runs on tokio's blocking thread pool, so devices are picked exactly like [``Device::new``] picks them,
+/// whereas reads and the waits between sweep steps use tokio's file system and timer facilities.
+/// Writes go through the same writer as [``Device::write_value``] on the blocking thread pool, so they fall back to logind just the same.
+/// # Examples
+/// ```ignore
+/// let mut bl = AsyncDevice::load(None).await?;
+/// bl.sweep_write(bl.calculate_change(10, Direction::Inc), Delay::default()).await?;
+/// bl.reload().await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsyncDevice {
+    device: Device,
+}
+
+impl AsyncDevice {
+    /// Async counterpart of [``Device::new``]
+    /// # Errors
+    /// Same as [``Device::new``]
+    pub async fn load(name: Option<Cow<'_, str>>) -> BlResult<AsyncDevice> {
+        Self::load_in(SYSFS, name).await
+    }
+
+    /// Async counterpart of [``Device::new_in``]
+    /// # Errors
+    /// Same as [``Device::new``]
+    pub async fn load_in(
+        root: impl AsRef<Path>,
+        name: Option<Cow<'_, str>>,
+    ) -> BlResult<AsyncDevice> {
+        let root = root.as_ref().to_path_buf();
+        let name = name.map(Cow::into_owned);
+        let device = blocking(move || Device::new_in(root, name.map(Cow::from))).await?;
+        Ok(Self { device })
+    }
+
+    /// Returns the name of the current device
+    #[must_use]
+    pub fn name(&self) -> &str {
+        self.device.name()
+    }
+
+    /// Returns the current brightness value of the current device
+    #[must_use]
+    pub fn current(&self) -> u32 {
+        self.device.current()
+    }
+
+    /// Returns the max brightness value of the current device
+    #[must_use]
+    pub fn max(&self) -> u32 {
+        self.device.max()
+    }
+
+    /// Returns the current brightness of the current device as a percentage of its max brightness
+    #[must_use]
+    pub fn current_percent(&self) -> f64 {
+        self.device.current_percent()
+    }
+
+    /// Returns the location of the device directory
+    #[must_use]
+    pub fn device_path(&self) -> &Path {
+        self.device.device_path()
+    }
+
+    /// See [``Backlight::calculate_change``]
+    #[must_use]
+    pub fn calculate_change(&self, step_size: u32, dir: Direction) -> u32 {
+        self.device.calculate_change(step_size, dir)
+    }
+
+    fn brightness_file(&self) -> PathBuf {
+        self.device.path.join("brightness")
+    }
+
+    /// Reloads current value for the current device in place.
+    /// # Errors
+    /// * [``BlibError::ReadCurrent``]
+    pub async fn reload(&mut self) -> BlResult<()> {
+        self.device.current = fs::read_to_string(self.brightness_file())
+            .await
+            .or(Err(BlibError::ReadCurrent))?
+            .trim()
+            .parse()
+            .or(Err(BlibError::ReadCurrent))?;
+        Ok(())
+    }
+
+    /// Async counterpart of [``Device::write_value``]
+    /// # Errors
+    /// * [``BlibError::ValueTooLarge``]
+    /// * [``BlibError::WriteNewVal``] - on write failure
+    pub async fn write_value(&self, value: u32) -> BlResult<()> {
+        check_max(value, self.device.max)?;
+        let device = self.device.clone();
+        blocking(move || device.write_value(value)).await
+    }
+
+    /// Async counterpart of [``Backlight::sweep_write``], which waits on tokio's timer between the steps instead of blocking the thread.
+    /// # Errors
+    /// * [``BlibError::ValueTooLarge``]
+    /// * [``BlibError::SweepError``]
+    pub async fn sweep_write(&self, value: u32, delay: Delay) -> BlResult<()> {
+        check_max(value, self.device.max)?;
+        let value = self.device.floor(value);
+        let device = self.device.clone();
+        let mut writer = blocking(move || device.open_writer())
+            .await
+            .map_err(BlibError::SweepError)?;
+        let rate = self.device.sweep_rate();
+        let mut current = self.device.current;
+        while current != value {
+            current = step_towards(current, value, rate);
+            // the writer is handed to the blocking thread pool and back on every step
+            writer = blocking(move || writer.write(current).map(|()| writer))
+                .await
+                .map_err(BlibError::SweepError)?;
+            time::sleep(*delay).await;
+        }
+        Ok(())
+    }
+}
+
+/// Runs blocking I/O on tokio's blocking thread pool, passing panics on to the caller
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    task::spawn_blocking(f)
+        .await
+        .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()))
+}
+
+impl From<Device> for AsyncDevice {
+    fn from(device: Device) -> Self {
+        Self { device }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixture::Fixture, BLCLASS};
+
+    #[tokio::test]
+    async fn async_device() {
+        let root = Fixture::new("async");
+        root.device(BLCLASS, "amdgpu_bl0", 100, 255);
+
+        let mut bl = AsyncDevice::load_in(&root, None).await.unwrap();
+        assert_eq!(bl.name(), "amdgpu_bl0");
+        assert_eq!((bl.current(), bl.max()), (100, 255));
+
+        bl.write_value(20).await.unwrap();
+        bl.reload().await.unwrap();
+        assert_eq!(bl.current(), 20);
+
+        bl.sweep_write(30, Delay::from_millis(1)).await.unwrap();
+        bl.reload().await.unwrap();
+        assert_eq!(bl.current(), 30);
+        assert!(bl.write_value(256).await.is_err());
+
+        assert!(matches!(
+            AsyncDevice::load_in(&root, Some("nvidia_0".into())).await,
+            Err(BlibError::NoDeviceFound)
+        ));
+    }
+}
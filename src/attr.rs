@@ -0,0 +1,139 @@
+//! Typed access to the optional sysfs attributes of a backlight device. See [``Device::actual_brightness``][crate::Device::actual_brightness],
+//! [``Device::bl_power``][crate::Device::bl_power], [``Device::backlight_type``][crate::Device::backlight_type] and [``Device::scale``][crate::Device::scale]
+
+use crate::{Device, Scale};
+use std::{fmt, fs, path::Path};
+
+/// The kind of interface a backlight device is controlled through, as read from its `type` attribute.
+///
+/// The kernel documentation recommends preferring firmware over platform over raw interfaces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BacklightType {
+    /// Controlled through a standard firmware interface, like ACPI video
+    Firmware,
+    /// Controlled through a platform specific interface, like a vendor's laptop driver
+    Platform,
+    /// Controlled by writing directly to the hardware registers, usually through the GPU driver
+    Raw,
+}
+
+impl BacklightType {
+    pub(crate) fn read(device_dir: &Path) -> Option<Self> {
+        match read_attr(device_dir, "type")?.as_str() {
+            "firmware" => Some(Self::Firmware),
+            "platform" => Some(Self::Platform),
+            "raw" => Some(Self::Raw),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BacklightType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Firmware => "firmware",
+            Self::Platform => "platform",
+            Self::Raw => "raw",
+        })
+    }
+}
+
+/// Power state of a backlight device, as read from its `bl_power` attribute
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlPower {
+    /// The backlight is powered (`FB_BLANK_UNBLANK`)
+    On,
+    /// The backlight is blanked or powered down, with the raw blanking level reported by the kernel
+    Off(u32),
+}
+
+impl fmt::Display for BlPower {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::On => f.write_str("on"),
+            Self::Off(level) => write!(f, "off ({level})"),
+        }
+    }
+}
+
+impl Device {
+    /// Returns the brightness value the hardware actually applied, read from the `actual_brightness` attribute.
+    ///
+    /// This can differ from [``Backlight::current``][crate::Backlight::current] when a write was ignored or adjusted by the firmware.
+    /// Returns None if the device doesn't expose the attribute or it can't be read.
+    #[must_use]
+    pub fn actual_brightness(&self) -> Option<u32> {
+        read_attr(&self.path, "actual_brightness")?.parse().ok()
+    }
+
+    /// Returns the power state of the backlight, read from the `bl_power` attribute.
+    /// Returns None if the device doesn't expose the attribute or it can't be read.
+    #[must_use]
+    pub fn bl_power(&self) -> Option<BlPower> {
+        match read_attr(&self.path, "bl_power")?.parse().ok()? {
+            0 => Some(BlPower::On),
+            level => Some(BlPower::Off(level)),
+        }
+    }
+
+    /// Returns the kind of interface the device is controlled through, read from the `type` attribute.
+    /// Returns None if the device doesn't expose the attribute or it holds an unknown value.
+    #[must_use]
+    pub fn backlight_type(&self) -> Option<BacklightType> {
+        BacklightType::read(&self.path)
+    }
+
+    /// Returns how brightness values of the device map to light output, read from the `scale` attribute.
+    #[must_use]
+    pub fn scale(&self) -> Scale {
+        Scale::read(&self.path)
+    }
+}
+
+/// Reads a sysfs attribute of a device, with surrounding whitespace removed
+pub(crate) fn read_attr(device_dir: &Path, attr: &str) -> Option<String> {
+    fs::read_to_string(device_dir.join(attr))
+        .ok()
+        .map(|value| value.trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixture::Fixture, BrightnessCurve};
+
+    #[test]
+    fn reading_attributes() {
+        let root = Fixture::new("attrs");
+        let path = root.join("acpi_video0");
+        fs::create_dir_all(&path).unwrap();
+        let d = Device {
+            name: "acpi_video0".into(),
+            current: 5,
+            max: 7,
+            path: path.clone(),
+            curve: BrightnessCurve::Linear,
+            min: 0,
+            #[cfg(feature = "logind")]
+            session: None,
+        };
+        assert_eq!(d.actual_brightness(), None);
+        assert_eq!(d.bl_power(), None);
+        assert_eq!(d.backlight_type(), None);
+        assert_eq!(d.scale(), Scale::Unknown);
+
+        fs::write(path.join("actual_brightness"), "4\n").unwrap();
+        fs::write(path.join("bl_power"), "0\n").unwrap();
+        fs::write(path.join("type"), "firmware\n").unwrap();
+        fs::write(path.join("scale"), "non-linear\n").unwrap();
+        assert_eq!(d.actual_brightness(), Some(4));
+        assert_eq!(d.bl_power(), Some(BlPower::On));
+        assert_eq!(d.backlight_type(), Some(BacklightType::Firmware));
+        assert_eq!(d.scale(), Scale::NonLinear);
+
+        fs::write(path.join("bl_power"), "4\n").unwrap();
+        fs::write(path.join("type"), "raw\n").unwrap();
+        assert_eq!(d.bl_power(), Some(BlPower::Off(4)));
+        assert_eq!(d.backlight_type(), Some(BacklightType::Raw));
+    }
+}
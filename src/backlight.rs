@@ -0,0 +1,357 @@
+//! The [`Backlight`] trait, which lets any brightness control share blight's change, sweep and percentage logic
+
+use crate::{
+    err::BlibError, sweep, BlResult, BrightnessCurve, Change, Delay, Direction, Easing, Value,
+};
+use std::{thread, time::Duration};
+
+/// Something whose brightness can be read and written as raw values between 0 and a max value.
+///
+/// Implementors only provide the name, the current and max values and a way to write a raw value,
+/// everything else (percentages, step calculation along a [curve][BrightnessCurve], sweeps) comes with the trait.
+/// [``Device``][crate::Device] implements it for sysfs backlights and LEDs, but other backends (like a DDC/CI monitor or a mock in tests)
+/// can implement it just as well and are then usable with the same helpers.
+/// # Examples
+/// ```ignore
+/// use blight::{Backlight, BlResult, Change, Direction};
+///
+/// fn brighten(bl: &impl Backlight) -> BlResult<()> {
+///     bl.change(10, Change::Sweep, Direction::Inc)
+/// }
+/// ```
+pub trait Backlight {
+    /// Returns the name of the device
+    fn name(&self) -> &str;
+
+    /// Returns the current brightness value of the device
+    fn current(&self) -> u32;
+
+    /// Returns the max brightness value of the device
+    fn max(&self) -> u32;
+
+    /// Writes a raw brightness value to the device.
+    /// # Errors
+    /// Implementations are expected to return [``BlibError::ValueTooLarge``] for values larger than max,
+    /// and [``BlibError::WriteNewVal``] if the value can't be written.
+    fn write_raw(&self, value: u32) -> BlResult<()>;
+
+    /// Returns the curve steps are applied along by [``Backlight::calculate_change``], linear unless the implementor knows better
+    fn curve(&self) -> BrightnessCurve {
+        BrightnessCurve::Linear
+    }
+
+    /// Returns the floor that [``Backlight::calculate_change``], [``Backlight::set_percent``] and sweeps don't go below, 0 unless the implementor knows better.
+    ///
+    /// Many panels turn fully black at 0, a floor keeps them usable. [``Backlight::write_raw``] doesn't enforce it, so any value can still be written on purpose.
+    fn min(&self) -> u32 {
+        0
+    }
+
+    /// Raises a value below the [floor][Backlight::min] to the floor.
+    ///
+    /// If the device is already below the floor (because something else changed it), the value is only raised to the current value,
+    /// so that a decrease never makes the device brighter.
+    fn floor(&self, value: u32) -> u32 {
+        value.max(self.min().min(self.current()).min(self.max()))
+    }
+
+    /// Returns the current brightness as a percentage of max brightness
+    fn current_percent(&self) -> f64 {
+        self.to_percent(self.current())
+    }
+
+    /// Converts a raw value of the device to a percentage of its max brightness.
+    ///
+    /// Values larger than max are not clamped, so the result can be larger than 100.
+    fn to_percent(&self, raw: u32) -> f64 {
+        if self.max() == 0 {
+            return 0.0;
+        }
+        f64::from(raw) * 100.0 / f64::from(self.max())
+    }
+
+    /// Converts the given [Value] to a raw value of the device.
+    ///
+    /// Percentages are clamped to the 0-100 range and rounded to the nearest raw value, raw values are returned as they are.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn to_raw(&self, value: Value) -> u32 {
+        match value {
+            Value::Raw(raw) => raw,
+            Value::Percent(p) => {
+                let p = if p.is_nan() { 0.0 } else { p.clamp(0.0, 100.0) };
+                (p / 100.0 * f64::from(self.max())).round() as u32
+            }
+        }
+    }
+
+    /// Calculates the new value to be written to the device based on the provided step-size (percentage) and direction,
+    /// using the current and max values of the device. (Always guaranteed to be valid)
+    ///
+    /// For example, if the current value is 10 and max is 100, and you want to increase it by 10% (`step_size`),
+    /// the method will return 20, which can be directly written to the device.
+    ///
+    /// The step is applied along the device's [curve][Backlight::curve], use [``Backlight::calculate_change_with``] to use a different one.
+    /// The result doesn't go below the device's [floor][Backlight::min].
+    ///
+    /// A non-zero step always changes the value by at least one raw unit (unless the value is already at the bound), even when the step is smaller than a single
+    /// brightness level of the device. This matters on devices that only expose a handful of levels, like ACPI devices with a max of 7 or 15.
+    fn calculate_change(&self, step_size: u32, dir: Direction) -> u32 {
+        self.calculate_change_with(step_size, dir, self.curve())
+    }
+
+    /// Same as [``Backlight::calculate_change``], except that the step is applied along the given [curve][BrightnessCurve].
+    ///
+    /// The current value is first mapped to perceived brightness, the step is added to or subtracted from it, and the result is mapped back to a raw value.
+    fn calculate_change_with(&self, step_size: u32, dir: Direction, curve: BrightnessCurve) -> u32 {
+        perceived_step(self, f64::from(step_size), dir, curve)
+    }
+
+    /// Same as [``Backlight::calculate_change``], except that the step is a [Value].
+    ///
+    /// Percentages can be fractional and are applied along the device's [curve][Backlight::curve],
+    /// raw steps move the value by exactly that many raw units. Either way, the result stays between the [floor][Backlight::min] and max.
+    fn calculate_change_by(&self, step: Value, dir: Direction) -> u32 {
+        let (current, max) = (self.current(), self.max());
+        match step {
+            Value::Percent(percent) => perceived_step(self, percent, dir, self.curve()),
+            Value::Raw(raw) => self.floor(match dir {
+                Direction::Inc => current.saturating_add(raw).min(max),
+                Direction::Dec => current.saturating_sub(raw).min(max),
+            }),
+        }
+    }
+
+    /// Sets the brightness to the given percentage of max brightness, see [``Backlight::to_raw``].
+    /// Percentages below the [floor][Backlight::min] set the floor instead.
+    /// # Errors
+    /// Same as [``Backlight::write_raw``]
+    fn set_percent(&self, percent: f64) -> BlResult<()> {
+        self.write_raw(self.floor(self.to_raw(Value::Percent(percent))))
+    }
+
+    /// Writes values starting from the current value in a loop, moving 1% closer to the target value on each iteration with some delay in between,
+    /// creating a smooth brightness transition.
+    ///
+    /// On devices with less than 100 brightness levels, each iteration moves by a single raw unit instead, so the sweep always makes progress.
+    /// Nothing is written if the target is the same as the current value or is larger than the max value.
+    /// Targets below the [floor][Backlight::min] are raised to it, see [``Backlight::floor``].
+    /// # Errors
+    /// Same as [``Backlight::write_raw``]
+    fn sweep_write(&self, value: u32, delay: Delay) -> BlResult<()> {
+        step_sweep(self.current(), self.floor(value), self.max(), delay, |v| {
+            self.write_raw(v)
+        })
+    }
+
+    /// Moves from the current value to the target value over the given duration,
+    /// creating a smooth brightness transition that always takes the same amount of time, regardless of the distance travelled.
+    ///
+    /// Intermediate values are written every 25ms (see [``Delay::default``]) and computed with the given [Easing] function,
+    /// the target value itself is written once the duration has elapsed.
+    /// Values that would be the same as the last written one are skipped, which matters on devices with few brightness levels.
+    ///
+    /// Note: Nothing is written if the provided value is the same as current brightness value.
+    /// Targets below the [floor][Backlight::min] are raised to it, see [``Backlight::floor``].
+    /// # Example
+    /// ```ignore
+    /// Device::new(None)?
+    ///     .sweep_write_for(50, Duration::from_millis(300), Easing::EaseOut)?;
+    /// ```
+    /// # Errors
+    /// * [``BlibError::ValueTooLarge``]
+    /// * Same as [``Backlight::write_raw``]
+    fn sweep_write_for(&self, value: u32, duration: Duration, easing: Easing) -> BlResult<()> {
+        sweep::timed_sweep(self, value, duration, easing, |v| self.write_raw(v))
+    }
+
+    /// Changes the brightness by the given step-size (percentage) in the given [Direction], either instantly or as a sweep, depending on the [Change] type.
+    /// Nothing is written if the calculated value is the same as the current value.
+    /// # Errors
+    /// Same as [``Backlight::write_raw``]
+    fn change(&self, step_size: u32, ch: Change, dir: Direction) -> BlResult<()> {
+        let change = self.calculate_change(step_size, dir);
+        if change == self.current() {
+            return Ok(());
+        }
+        match ch {
+            Change::Sweep => self.sweep_write(change, Delay::default()),
+            Change::Regular => self.write_raw(change),
+        }
+    }
+}
+
+/// Applies a percentage step along the curve, see [``Backlight::calculate_change_with``]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn perceived_step<B: Backlight + ?Sized>(
+    bl: &B,
+    percent: f64,
+    dir: Direction,
+    curve: BrightnessCurve,
+) -> u32 {
+    let (current, max) = (bl.current(), bl.max());
+    if max == 0 {
+        return 0;
+    }
+    let maxf = f64::from(max);
+    let step = if percent.is_nan() {
+        0.0
+    } else {
+        percent.max(0.0) / 100.0
+    };
+    let perceived = curve.to_perceived(f64::from(current) / maxf);
+    let target = match dir {
+        Direction::Inc => perceived + step,
+        Direction::Dec => perceived - step,
+    };
+    let change = (curve.to_raw(target) * maxf).round() as u32;
+    bl.floor(match dir {
+        _ if step == 0.0 => change.min(max),
+        Direction::Inc => change.max(current.saturating_add(1)).min(max),
+        Direction::Dec => change.min(current.saturating_sub(1)),
+    })
+}
+
+/// Step size of step based sweeps: 1% of max, but at least one raw unit
+pub(crate) fn sweep_rate(max: u32) -> u32 {
+    (max / 100).max(1)
+}
+
+/// Drives a step based sweep, handing every intermediate value to the writer
+pub(crate) fn step_sweep(
+    mut current: u32,
+    value: u32,
+    max: u32,
+    delay: Delay,
+    mut write: impl FnMut(u32) -> BlResult<()>,
+) -> BlResult<()> {
+    if value > max {
+        return Ok(());
+    }
+    while current != value {
+        current = sweep::step_towards(current, value, sweep_rate(max));
+        write(current)?;
+        thread::sleep(*delay);
+    }
+    Ok(())
+}
+
+/// Checks the value against the max value, as expected from [``Backlight::write_raw``]
+pub(crate) fn check_max(value: u32, max: u32) -> BlResult<()> {
+    if value > max {
+        return Err(BlibError::ValueTooLarge {
+            given: value,
+            supported: max,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct Fake {
+        current: u32,
+        min: u32,
+        writes: Mutex<Vec<u32>>,
+    }
+
+    impl Backlight for Fake {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn current(&self) -> u32 {
+            self.current
+        }
+
+        fn max(&self) -> u32 {
+            10
+        }
+
+        fn write_raw(&self, value: u32) -> BlResult<()> {
+            check_max(value, self.max())?;
+            self.writes.lock().unwrap().push(value);
+            Ok(())
+        }
+
+        fn min(&self) -> u32 {
+            self.min
+        }
+    }
+
+    #[test]
+    fn provided_methods() {
+        let fake = Fake {
+            current: 5,
+            min: 0,
+            writes: Mutex::new(Vec::new()),
+        };
+        let writes = || fake.writes.lock().unwrap().clone();
+        assert!((fake.current_percent() - 50.0).abs() < f64::EPSILON);
+        assert_eq!(fake.to_raw(Value::Percent(20.0)), 2);
+
+        fake.change(10, Change::Regular, Direction::Inc).unwrap();
+        // every provided method can be called through a trait object
+        let bl: &dyn Backlight = &fake;
+        bl.set_percent(100.0).unwrap();
+        assert_eq!(writes(), [6, 10]);
+
+        fake.sweep_write(2, Delay::from_millis(1)).unwrap();
+        assert_eq!(writes()[2..], [4, 3, 2]);
+        fake.sweep_write_for(8, Duration::from_millis(50), Easing::Linear)
+            .unwrap();
+        assert_eq!(writes().last(), Some(&8));
+        assert!(matches!(
+            fake.sweep_write_for(11, Duration::ZERO, Easing::Linear),
+            Err(BlibError::ValueTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn floor() {
+        let fake = Fake {
+            current: 3,
+            min: 2,
+            writes: Mutex::new(Vec::new()),
+        };
+        let writes = || fake.writes.lock().unwrap().clone();
+        assert_eq!(fake.calculate_change(10, Direction::Dec), 2);
+        assert_eq!(fake.calculate_change(50, Direction::Dec), 2);
+        fake.set_percent(0.0).unwrap();
+        fake.sweep_write(0, Delay::from_millis(1)).unwrap();
+        assert_eq!(writes(), [2, 2]);
+        fake.write_raw(0).unwrap();
+
+        // Below the floor already, a decrease doesn't make it brighter
+        let dim = Fake { current: 1, ..fake };
+        assert_eq!(dim.calculate_change(10, Direction::Dec), 1);
+        assert_eq!(dim.calculate_change(10, Direction::Inc), 2);
+    }
+
+    #[test]
+    fn value_steps() {
+        let fake = Fake {
+            current: 5,
+            min: 1,
+            writes: Mutex::new(Vec::new()),
+        };
+        assert_eq!(fake.calculate_change_by(Value::Raw(3), Direction::Inc), 8);
+        assert_eq!(fake.calculate_change_by(Value::Raw(30), Direction::Inc), 10);
+        assert_eq!(fake.calculate_change_by(Value::Raw(30), Direction::Dec), 1);
+        assert_eq!(
+            fake.calculate_change_by(Value::Percent(20.0), Direction::Dec),
+            3
+        );
+        // A fraction of a level still moves by a raw unit, like whole percentages do
+        assert_eq!(
+            fake.calculate_change_by(Value::Percent(0.5), Direction::Inc),
+            6
+        );
+        assert_eq!(
+            fake.calculate_change_by(Value::Percent(10.0), Direction::Inc),
+            fake.calculate_change(10, Direction::Inc)
+        );
+    }
+}
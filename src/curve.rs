@@ -0,0 +1,139 @@
+//! Brightness curves used to map raw backlight values to perceived brightness. See [`BrightnessCurve`]
+
+use crate::attr::read_attr;
+use std::{fmt, path::Path};
+
+/// Base of the logarithmic curve. Larger values give finer steps at the lower end of the range.
+const LOG_BASE: f64 = 100.0;
+
+/// The gamma exponent used when `gamma` is picked without specifying one.
+pub const DEFAULT_GAMMA: f64 = 2.2;
+
+/// A curve that maps the raw brightness of a device to the brightness perceived by the eye, and back.
+///
+/// Both directions work on fractions of the max brightness in the range 0.0-1.0. Step changes (see [``Backlight::calculate_change``][crate::Backlight::calculate_change])
+/// are applied in the perceived space, which means that with a non-linear curve, a 5% step is small at low brightness and large at high brightness in raw terms,
+/// but roughly the same for the eye.
+///
+/// The default curve of a device follows the kernel's `scale` attribute, see [``BrightnessCurve::for_scale``].
+#[derive(Default, Clone, Copy, Debug)]
+pub enum BrightnessCurve {
+    /// Raw values are treated as perceived values (the behavior of blight before curves were introduced)
+    #[default]
+    Linear,
+    /// Perceived brightness grows with the logarithm of the raw value
+    Logarithmic,
+    /// Raw value is the perceived value raised to the given exponent, see [``DEFAULT_GAMMA``]
+    Gamma(f64),
+    /// User provided mapping, both functions should be monotonic and map 0.0 to 0.0 and 1.0 to 1.0
+    Custom {
+        to_perceived: fn(f64) -> f64,
+        to_raw: fn(f64) -> f64,
+    },
+}
+
+impl BrightnessCurve {
+    /// Picks a curve based on how the kernel describes the brightness scale of a device.
+    ///
+    /// Devices with a linear scale control the light output directly, so they get a [logarithmic][BrightnessCurve::Logarithmic] curve.
+    /// Non-linear devices are already perceptually scaled by the firmware, so they get a [linear][BrightnessCurve::Linear] curve, as do devices with an unknown scale.
+    #[must_use]
+    pub fn for_scale(scale: Scale) -> Self {
+        match scale {
+            Scale::Linear => Self::Logarithmic,
+            Scale::NonLinear | Scale::Unknown => Self::Linear,
+        }
+    }
+
+    /// Converts a raw fraction of max brightness to a perceived fraction
+    #[must_use]
+    pub fn to_perceived(self, raw: f64) -> f64 {
+        let raw = raw.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => raw,
+            Self::Logarithmic => (raw * (LOG_BASE - 1.0)).ln_1p() / LOG_BASE.ln(),
+            Self::Gamma(exp) => raw.powf(exp.recip()),
+            Self::Custom { to_perceived, .. } => to_perceived(raw),
+        }
+    }
+
+    /// Converts a perceived fraction of max brightness to a raw fraction
+    #[must_use]
+    pub fn to_raw(self, perceived: f64) -> f64 {
+        let perceived = perceived.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => perceived,
+            Self::Logarithmic => (LOG_BASE.powf(perceived) - 1.0) / (LOG_BASE - 1.0),
+            Self::Gamma(exp) => perceived.powf(exp),
+            Self::Custom { to_raw, .. } => to_raw(perceived),
+        }
+    }
+}
+
+/// How the kernel describes the brightness scale of a device, as read from its `scale` attribute.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scale {
+    #[default]
+    Unknown,
+    Linear,
+    NonLinear,
+}
+
+impl Scale {
+    /// Reads the `scale` attribute from the given device directory. Missing or unrecognised values result in [``Scale::Unknown``].
+    pub(crate) fn read(device_dir: &Path) -> Self {
+        match read_attr(device_dir, "scale").as_deref() {
+            Some("linear") => Self::Linear,
+            Some("non-linear") => Self::NonLinear,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for Scale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Unknown => "unknown",
+            Self::Linear => "linear",
+            Self::NonLinear => "non-linear",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves_round_trip() {
+        for curve in [
+            BrightnessCurve::Linear,
+            BrightnessCurve::Logarithmic,
+            BrightnessCurve::Gamma(DEFAULT_GAMMA),
+        ] {
+            for i in 0..=100 {
+                let raw = f64::from(i) / 100.0;
+                let back = curve.to_raw(curve.to_perceived(raw));
+                assert!((back - raw).abs() < 1e-9, "{curve:?} {raw} -> {back}");
+            }
+            assert!(curve.to_perceived(0.0).abs() < 1e-9);
+            assert!((curve.to_perceived(1.0) - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn scale_defaults() {
+        assert!(matches!(
+            BrightnessCurve::for_scale(Scale::Linear),
+            BrightnessCurve::Logarithmic
+        ));
+        assert!(matches!(
+            BrightnessCurve::for_scale(Scale::NonLinear),
+            BrightnessCurve::Linear
+        ));
+        assert!(matches!(
+            BrightnessCurve::for_scale(Scale::Unknown),
+            BrightnessCurve::Linear
+        ));
+    }
+}
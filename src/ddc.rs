@@ -0,0 +1,510 @@
+//! Brightness control of external monitors over DDC/CI. See [``Monitor``]
+//!
+//! Monitors are reached through the I2C bus of the DRM connector they're plugged into, like `/dev/i2c-5` for `card0-DP-2`,
+//! which requires the `i2c-dev` kernel module and read/write access to the bus device.
+
+use crate::{
+    attr::read_attr, backlight::check_max, drm::INTERNAL_TYPES, err::BlibError, Backlight,
+    BlResult, BrightnessCurve, SYSFS,
+};
+use rustix::ioctl::{ioctl, BadOpcode, IntegerSetter};
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    thread,
+    time::Duration,
+};
+
+/// Location of DRM connectors, relative to the sysfs root
+const DRMCLASS: &str = "class/drm";
+/// Location of I2C bus device nodes
+const DEVDIR: &str = "/dev";
+/// The ioctl that sets the address of the device the following reads and writes go to
+const I2C_SLAVE: u32 = 0x0703;
+/// I2C address of the DDC/CI interface of a monitor
+const DDC_ADDR: usize = 0x37;
+/// VCP feature code of the luminance control
+const LUMINANCE: u8 = 0x10;
+/// Time the monitor needs to process a message before the next one
+const DDC_DELAY: Duration = Duration::from_millis(50);
+/// Reads are retried this many times, since monitors sometimes answer with garbage
+const READ_ATTEMPTS: usize = 3;
+
+/// The link to a monitor's DDC/CI interface, which carries raw DDC/CI messages.
+///
+/// [``I2cBus``] is the real thing, other implementations can stand in for a monitor in tests.
+pub trait Transport: Send {
+    /// Sends a message to the monitor
+    /// # Errors
+    /// If the message can't be written
+    fn write(&mut self, data: &[u8]) -> io::Result<()>;
+
+    /// Reads a reply from the monitor, filling the whole buffer
+    /// # Errors
+    /// If no reply can be read
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<()>;
+}
+
+/// An I2C bus device node, like `/dev/i2c-5`, addressed to the DDC/CI interface of the monitor on it
+#[derive(Debug)]
+pub struct I2cBus {
+    file: File,
+}
+
+impl I2cBus {
+    /// Opens the bus device node at the given path
+    /// # Errors
+    /// If the device node can't be opened for reading and writing or isn't an I2C bus
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::options().read(true).write(true).open(path)?;
+        // SAFETY: I2C_SLAVE takes the 7-bit address of the device as an integer argument
+        unsafe { ioctl(&file, IntegerSetter::<BadOpcode<I2C_SLAVE>>::new(DDC_ADDR)) }?;
+        Ok(Self { file })
+    }
+}
+
+impl Transport for I2cBus {
+    /// Writes the message and waits until the monitor has had time to process it
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.file.write_all(data)?;
+        thread::sleep(DDC_DELAY);
+        Ok(())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        self.file.read_exact(buf)
+    }
+}
+
+/// An external monitor whose brightness is controlled over DDC/CI, through VCP feature 0x10 (luminance).
+///
+/// It implements [``Backlight``], so it can be changed and swept like a [``Device``][crate::Device].
+/// Every DDC/CI message takes around 50ms, which makes sweeps noticeably slower than on internal panels.
+/// # Examples
+/// ```ignore
+/// let monitor = Monitor::open("DP-2")?;
+/// monitor.set_percent(50.0)?;
+/// ```
+pub struct Monitor {
+    name: String,
+    current: u32,
+    max: u32,
+    curve: BrightnessCurve,
+    min: u32,
+    /// Device node of the I2C bus, None for monitors created with [``Monitor::new``]
+    bus: Option<PathBuf>,
+    transport: Mutex<Box<dyn Transport>>,
+}
+
+impl Monitor {
+    /// Opens the monitor plugged into the given output, like `DP-2` or `card0-DP-2`, see [``ddc_outputs``]
+    /// # Errors
+    /// * [``BlibError::ReadDrmDir``] - if the DRM directory can't be read
+    /// * [``BlibError::NoDeviceFound``] - if no connected output with an I2C bus has the given name
+    /// * [``BlibError::DdcError``] - if the monitor can't be reached or doesn't support brightness control
+    pub fn open(output: &str) -> BlResult<Self> {
+        Self::open_in(SYSFS, output)
+    }
+
+    /// Same as [``Monitor::open``], except that outputs are looked up under the given sysfs root, see [``Device::new_in``][crate::Device::new_in]
+    /// # Errors
+    /// Same as [``Monitor::open``]
+    pub fn open_in(root: impl AsRef<Path>, output: &str) -> BlResult<Self> {
+        let output = ddc_outputs_in(root)?
+            .into_iter()
+            .find(|o| o.matches(output))
+            .ok_or(BlibError::NoDeviceFound)?;
+        let bus = I2cBus::open(&output.bus).map_err(|err| BlibError::DdcError {
+            err,
+            dev: output.output.clone(),
+            bus: Some(output.bus.clone()),
+        })?;
+        let mut monitor = Self::create(output.output, bus);
+        monitor.bus = Some(output.bus);
+        monitor.reload()?;
+        Ok(monitor)
+    }
+
+    /// Creates a monitor that is reached through the given transport, reading its current and max brightness
+    /// # Errors
+    /// * [``BlibError::DdcError``] - if the values can't be read
+    pub fn new(name: impl Into<String>, transport: impl Transport + 'static) -> BlResult<Self> {
+        let mut monitor = Self::create(name.into(), transport);
+        monitor.reload()?;
+        Ok(monitor)
+    }
+
+    fn create(name: String, transport: impl Transport + 'static) -> Self {
+        Self {
+            name,
+            current: 0,
+            max: 0,
+            curve: BrightnessCurve::Linear,
+            min: 0,
+            bus: None,
+            transport: Mutex::new(Box::new(transport)),
+        }
+    }
+
+    /// Sets the curve used by [``Backlight::calculate_change``], which is linear unless changed
+    pub fn set_curve(&mut self, curve: BrightnessCurve) {
+        self.curve = curve;
+    }
+
+    /// Sets the floor that changes don't go below, see [``Backlight::min``]. It's 0 unless changed.
+    pub fn set_min(&mut self, min: u32) {
+        self.min = min;
+    }
+
+    /// Reads the current and max brightness from the monitor again.
+    ///
+    /// Unlike [``Device::reload``][crate::Device::reload] this returns an error instead of panicking, since DDC/CI reads fail much more often than sysfs reads.
+    /// # Errors
+    /// * [``BlibError::DdcError``] - if the values can't be read
+    pub fn reload(&mut self) -> BlResult<()> {
+        let (current, max) = self.read_luminance().map_err(|err| self.ddc_error(err))?;
+        self.current = u32::from(current);
+        self.max = u32::from(max);
+        Ok(())
+    }
+
+    fn ddc_error(&self, err: io::Error) -> BlibError {
+        BlibError::DdcError {
+            err,
+            dev: self.name.clone(),
+            bus: self.bus.clone(),
+        }
+    }
+
+    fn transport(&self) -> std::sync::MutexGuard<'_, Box<dyn Transport>> {
+        self.transport
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Asks the monitor for the luminance feature and returns its current and max values
+    fn read_luminance(&self) -> io::Result<(u16, u16)> {
+        let mut transport = self.transport();
+        let mut last_err = None;
+        for _ in 0..READ_ATTEMPTS {
+            transport.write(&request(&[0x01, LUMINANCE]))?;
+            let mut reply = [0; 11];
+            match transport
+                .read(&mut reply)
+                .and_then(|()| parse_reply(&reply))
+            {
+                Ok(values) => return Ok(values),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| io::ErrorKind::InvalidData.into()))
+    }
+}
+
+impl Backlight for Monitor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn current(&self) -> u32 {
+        self.current
+    }
+
+    fn max(&self) -> u32 {
+        self.max
+    }
+
+    /// Sets the luminance feature of the monitor to the value
+    /// # Errors
+    /// * [``BlibError::ValueTooLarge``]
+    /// * [``BlibError::DdcError``] - if the message can't be sent
+    fn write_raw(&self, value: u32) -> BlResult<()> {
+        check_max(value, self.max)?;
+        let [.., high, low] = value.to_be_bytes();
+        self.transport()
+            .write(&request(&[0x03, LUMINANCE, high, low]))
+            .map_err(|err| self.ddc_error(err))
+    }
+
+    fn curve(&self) -> BrightnessCurve {
+        self.curve
+    }
+
+    fn min(&self) -> u32 {
+        self.min
+    }
+}
+
+impl fmt::Debug for Monitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Monitor")
+            .field("name", &self.name)
+            .field("current", &self.current)
+            .field("max", &self.max)
+            .field("curve", &self.curve)
+            .field("min", &self.min)
+            .field("bus", &self.bus)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Wraps the payload in a DDC/CI message from the host: source address, length, payload and checksum
+#[allow(clippy::cast_possible_truncation)]
+fn request(payload: &[u8]) -> Vec<u8> {
+    let mut message = vec![0x51, 0x80 | payload.len() as u8];
+    message.extend_from_slice(payload);
+    // the checksum covers the destination address (0x37 << 1) too
+    message.push(message.iter().fold(0x6E, |chk, byte| chk ^ byte));
+    message
+}
+
+/// Parses the reply to a VCP feature request into the current and max values
+fn parse_reply(reply: &[u8; 11]) -> io::Result<(u16, u16)> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+    // replies are checksummed as if sent to the virtual host address 0x50
+    let checksum = reply[..10].iter().fold(0x50, |chk, byte| chk ^ byte);
+    if checksum != reply[10] {
+        return Err(invalid("checksum mismatch in DDC/CI reply"));
+    }
+    if reply[1] & 0x7F != 8 || reply[2] != 0x02 || reply[4] != LUMINANCE {
+        return Err(invalid("unexpected DDC/CI reply"));
+    }
+    if reply[3] != 0 {
+        return Err(invalid(
+            "monitor doesn't support brightness control over DDC/CI",
+        ));
+    }
+    Ok((
+        u16::from_be_bytes([reply[8], reply[9]]),
+        u16::from_be_bytes([reply[6], reply[7]]),
+    ))
+}
+
+/// A connected external output whose monitor may be reachable over DDC/CI, as returned by [``ddc_outputs``]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DdcOutput {
+    connector: String,
+    output: String,
+    bus: PathBuf,
+}
+
+impl DdcOutput {
+    /// Returns the full name of the connector, for example `card0-DP-2`
+    #[must_use]
+    pub fn connector(&self) -> &str {
+        &self.connector
+    }
+
+    /// Returns the name of the output, for example `DP-2`
+    #[must_use]
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// Returns the I2C bus device node of the output, for example `/dev/i2c-5`
+    #[must_use]
+    pub fn bus(&self) -> &Path {
+        &self.bus
+    }
+
+    /// Returns true if the given name refers to this output, either as an output (`DP-2`) or a connector name (`card0-DP-2`)
+    #[must_use]
+    pub fn matches(&self, name: &str) -> bool {
+        self.output == name || self.connector == name
+    }
+
+    /// Reads the connector from its directory, returns None if it's an internal panel, isn't connected or has no I2C bus
+    fn read(dir: &Path) -> Option<Self> {
+        let connector = dir.file_name()?.to_str()?;
+        let (card, output) = connector.split_once('-')?;
+        let kind = output.rsplit_once('-')?.0;
+        if !card.starts_with("card")
+            || INTERNAL_TYPES.contains(&kind)
+            || read_attr(dir, "status").as_deref() != Some("connected")
+        {
+            return None;
+        }
+        Some(Self {
+            connector: connector.to_owned(),
+            output: output.to_owned(),
+            bus: Path::new(DEVDIR).join(i2c_bus(dir)?),
+        })
+    }
+}
+
+impl fmt::Display for DdcOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.output, self.bus.display())
+    }
+}
+
+/// Finds the name of the connector's I2C bus, either from its `ddc` link or from an `i2c-N` directory (`DisplayPort` AUX channels)
+fn i2c_bus(dir: &Path) -> Option<String> {
+    let is_bus = |name: &str| {
+        name.strip_prefix("i2c-")
+            .is_some_and(|n| n.parse::<u32>().is_ok())
+    };
+    if let Some(name) = fs::canonicalize(dir.join("ddc"))
+        .ok()
+        .and_then(|linked| Some(linked.file_name()?.to_str()?.to_owned()))
+        .filter(|name| is_bus(name))
+    {
+        return Some(name);
+    }
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .find(|name| is_bus(name))
+}
+
+/// Returns every connected external output under [``SYSFS``] that has an I2C bus, ordered by connector name.
+///
+/// Monitors aren't probed, so an output being listed doesn't mean its monitor supports DDC/CI.
+/// # Errors
+/// * [``BlibError::ReadDrmDir``]
+pub fn ddc_outputs() -> BlResult<Vec<DdcOutput>> {
+    ddc_outputs_in(SYSFS)
+}
+
+/// Same as [``ddc_outputs``], except that outputs are looked up under the given sysfs root, see [``Device::new_in``][crate::Device::new_in]
+/// # Errors
+/// * [``BlibError::ReadDrmDir``]
+pub fn ddc_outputs_in(root: impl AsRef<Path>) -> BlResult<Vec<DdcOutput>> {
+    let mut outputs: Vec<_> = fs::read_dir(root.as_ref().join(DRMCLASS))
+        .map_err(BlibError::ReadDrmDir)?
+        .filter_map(|entry| DdcOutput::read(&entry.ok()?.path()))
+        .collect();
+    outputs.sort_by(|a, b| a.connector.cmp(&b.connector));
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixture::Fixture, Direction};
+    use std::{
+        os::unix::fs::symlink,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    /// Answers VCP requests like a monitor would, keeping the luminance in shared state
+    struct FakeMonitor {
+        luminance: Arc<Mutex<u16>>,
+        reply: Vec<u8>,
+        garbled: usize,
+        unplugged: Arc<AtomicBool>,
+    }
+
+    impl Transport for FakeMonitor {
+        fn write(&mut self, data: &[u8]) -> io::Result<()> {
+            if self.unplugged.load(Ordering::Relaxed) {
+                return Err(io::ErrorKind::NotConnected.into());
+            }
+            assert_eq!(data.iter().fold(0x6E, |chk, byte| chk ^ byte), 0);
+            let mut luminance = self.luminance.lock().unwrap();
+            match data[2..data.len() - 1] {
+                [0x01, LUMINANCE] => {
+                    let [mh, ml] = 100u16.to_be_bytes();
+                    let [ch, cl] = luminance.to_be_bytes();
+                    self.reply = vec![0x6E, 0x88, 0x02, 0, LUMINANCE, 0, mh, ml, ch, cl];
+                    let checksum = self.reply.iter().fold(0x50, |chk, byte| chk ^ byte);
+                    self.reply.push(checksum);
+                }
+                [0x03, LUMINANCE, high, low] => *luminance = u16::from_be_bytes([high, low]),
+                _ => panic!("unexpected request {data:x?}"),
+            }
+            Ok(())
+        }
+
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<()> {
+            buf.copy_from_slice(&self.reply);
+            if self.garbled > 0 {
+                self.garbled -= 1;
+                buf[10] ^= 0xFF;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn controlling_monitors() {
+        let luminance = Arc::new(Mutex::new(30));
+        let unplugged = Arc::new(AtomicBool::new(false));
+        let fake = FakeMonitor {
+            luminance: Arc::clone(&luminance),
+            reply: Vec::new(),
+            garbled: 2,
+            unplugged: Arc::clone(&unplugged),
+        };
+        let mut monitor = Monitor::new("DP-2", fake).unwrap();
+        assert_eq!((monitor.current(), monitor.max()), (30, 100));
+
+        monitor
+            .write_raw(monitor.calculate_change(20, Direction::Inc))
+            .unwrap();
+        assert_eq!(*luminance.lock().unwrap(), 50);
+        monitor.reload().unwrap();
+        assert_eq!(monitor.current(), 50);
+        assert!(matches!(
+            monitor.write_raw(101),
+            Err(BlibError::ValueTooLarge { .. })
+        ));
+        unplugged.store(true, Ordering::Relaxed);
+        assert!(matches!(
+            monitor.write_raw(40),
+            Err(BlibError::DdcError { bus: None, .. })
+        ));
+
+        let mut reply = [0x6E, 0x88, 0x02, 0x01, LUMINANCE, 0, 0, 0, 0, 0, 0];
+        reply[10] = reply[..10].iter().fold(0x50, |chk, byte| chk ^ byte);
+        assert!(parse_reply(&reply).is_err());
+    }
+
+    #[test]
+    fn finding_outputs() {
+        let root = Fixture::new("ddc");
+        let card = root.join("devices/pci0000:00/0000:01:00.0/drm/card0");
+        let i2c = root.join("devices/pci0000:00/0000:01:00.0/i2c-3");
+        fs::create_dir_all(&i2c).unwrap();
+        let connector = |name: &str, status: &str| {
+            let dir = card.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("status"), status).unwrap();
+            let class = root.join(DRMCLASS);
+            fs::create_dir_all(&class).unwrap();
+            symlink(fs::canonicalize(&dir).unwrap(), class.join(name)).unwrap();
+            dir
+        };
+        symlink(
+            fs::canonicalize(&i2c).unwrap(),
+            connector("card0-HDMI-A-1", "connected\n").join("ddc"),
+        )
+        .unwrap();
+        fs::create_dir(connector("card0-DP-2", "connected\n").join("i2c-7")).unwrap();
+        fs::create_dir(connector("card0-DP-1", "disconnected\n").join("i2c-6")).unwrap();
+        fs::create_dir(connector("card0-eDP-1", "connected\n").join("i2c-5")).unwrap();
+
+        let outputs = ddc_outputs_in(&root).unwrap();
+        let found: Vec<_> = outputs
+            .iter()
+            .map(|o| (o.output(), o.bus().to_str().unwrap()))
+            .collect();
+        assert_eq!(found, [("DP-2", "/dev/i2c-7"), ("HDMI-A-1", "/dev/i2c-3")]);
+        assert!(outputs[0].matches("card0-DP-2"));
+        assert!(matches!(
+            Monitor::open_in(&root, "DP-1"),
+            Err(BlibError::NoDeviceFound)
+        ));
+        assert!(matches!(
+            ddc_outputs_in(root.join("missing")),
+            Err(BlibError::ReadDrmDir(_))
+        ));
+    }
+}
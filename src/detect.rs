@@ -0,0 +1,274 @@
+//! Ranking of backlight devices, used by [``Device::new``][crate::Device::new] to pick a device when no name is given.
+//!
+//! Devices are ranked by their kernel `type` (firmware > platform > raw), as recommended by the kernel documentation. Among raw devices,
+//! the ones lighting up the internal panel (their eDP/LVDS connector is connected and enabled) are preferred,
+//! then the ones tied to the GPU that is driving the display, followed by the ones of integrated GPUs. Devices that rank the same are ordered by name,
+//! so the result never depends on the order in which the directory entries are returned.
+//!
+//! The GPU driving the display is found by asking `vga_switcheroo` first, which knows the position of the MUX on hybrid laptops.
+//! Without it (debugfs is usually only readable by root), GPUs that are powered down (PCI `enable` is 0) are ruled out,
+//! and if more than one is left, the one the firmware booted with (`boot_vga`) is assumed to drive the display.
+
+use crate::{
+    attr::read_attr, drm::Connector, err::BlibError, gpu::Gpu, BacklightType, BlResult, GpuKind,
+};
+use std::{cmp::Reverse, fs, path::Path};
+
+/// `vga_switcheroo` state, relative to the sysfs root
+const SWITCHEROO: &str = "kernel/debug/vgaswitcheroo/switch";
+
+/// A backlight device considered during detection, along with the facts it was ranked by
+#[derive(Debug, Clone)]
+pub(crate) struct Candidate {
+    pub(crate) name: String,
+    pub(crate) kind: Option<BacklightType>,
+    pub(crate) gpu: Option<Gpu>,
+    pub(crate) connector: Option<Connector>,
+    pub(crate) active_gpu: bool,
+}
+
+impl Candidate {
+    fn read(bldir: &Path, name: String) -> Self {
+        let path = bldir.join(&name);
+        Self {
+            kind: BacklightType::read(&path),
+            gpu: Gpu::find(&path),
+            connector: Connector::find(&path),
+            active_gpu: false,
+            name,
+        }
+    }
+
+    /// Lower sorts first. Devices without a type are only picked when nothing else is available.
+    fn key(&self) -> (u8, Reverse<bool>, Reverse<bool>, u8, &str) {
+        let kind = match self.kind {
+            Some(BacklightType::Firmware) => 0,
+            Some(BacklightType::Platform) => 1,
+            Some(BacklightType::Raw) => 2,
+            None => 3,
+        };
+        let raw = self.kind == Some(BacklightType::Raw);
+        let lit = raw && self.connector.as_ref().is_some_and(Connector::is_active);
+        let active = raw && self.active_gpu;
+        let gpu = match self.gpu.as_ref().map(Gpu::kind) {
+            Some(GpuKind::Integrated) => 0,
+            Some(GpuKind::Discrete) => 1,
+            None => 2,
+        };
+        (kind, Reverse(lit), Reverse(active), gpu, &self.name)
+    }
+}
+
+/// Returns all devices in the backlight directory, best candidate first
+pub(crate) fn rank(bldir: &Path) -> BlResult<Vec<Candidate>> {
+    let mut candidates: Vec<_> = fs::read_dir(bldir)
+        .map_err(BlibError::ReadBlDir)?
+        .filter_map(Result::ok)
+        .map(|entry| Candidate::read(bldir, entry.file_name().to_string_lossy().into_owned()))
+        .collect();
+    // bldir is {root}/class/backlight
+    let root = bldir.parent().and_then(Path::parent);
+    if let Some(active) = active_gpu(root, &candidates) {
+        for candidate in &mut candidates {
+            candidate.active_gpu = candidate.gpu.as_ref().map(Gpu::slot) == Some(&active);
+        }
+    }
+    candidates.sort_by(|a, b| a.key().cmp(&b.key()));
+    Ok(candidates)
+}
+
+/// Returns the PCI slot of the GPU that is driving the display, if it can be told
+fn active_gpu(root: Option<&Path>, candidates: &[Candidate]) -> Option<String> {
+    if let Some(slot) = root.and_then(switcheroo_active) {
+        return Some(slot);
+    }
+    let mut powered: Vec<&Gpu> = candidates
+        .iter()
+        .filter_map(|candidate| candidate.gpu.as_ref())
+        .filter(|gpu| read_attr(gpu.path(), "enable").as_deref() != Some("0"))
+        .collect();
+    powered.sort_by(|a, b| a.slot().cmp(b.slot()));
+    powered.dedup_by(|a, b| a.slot() == b.slot());
+    match powered[..] {
+        [gpu] => Some(gpu.slot().to_owned()),
+        _ => powered
+            .into_iter()
+            .find(|gpu| read_attr(gpu.path(), "boot_vga").as_deref() == Some("1"))
+            .map(|gpu| gpu.slot().to_owned()),
+    }
+}
+
+/// Reads the slot of the active client from `vga_switcheroo`, whose lines look like `0:IGD:+:Pwr:0000:00:02.0`
+fn switcheroo_active(root: &Path) -> Option<String> {
+    fs::read_to_string(root.join(SWITCHEROO))
+        .ok()?
+        .lines()
+        .find_map(|line| match line.splitn(5, ':').collect::<Vec<_>>()[..] {
+            [_, _, "+", _, slot] => Some(slot.to_owned()),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixture::Fixture, gpu::tests::pci_path};
+    use std::os::unix::fs::symlink;
+
+    fn add_device(root: &Path, name: &str, kind: &str, gpu: Option<(&str, &str, &str)>) {
+        let path = root.join("class/backlight").join(name);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("brightness"), "50").unwrap();
+        fs::write(path.join("type"), kind).unwrap();
+        if let Some((slot, vendor, class)) = gpu {
+            let gpu = pci_path(root, slot);
+            fs::create_dir_all(&gpu).unwrap();
+            fs::write(gpu.join("vendor"), vendor).unwrap();
+            fs::write(gpu.join("class"), class).unwrap();
+            symlink(fs::canonicalize(gpu).unwrap(), path.join("device")).unwrap();
+        }
+    }
+
+    #[test]
+    fn ranking_devices() {
+        let root = Fixture::new("detect");
+        add_device(
+            &root,
+            "nvidia_0",
+            "raw",
+            Some(("0000:01:00.0", "0x10de", "0x030000")),
+        );
+        add_device(
+            &root,
+            "intel_backlight",
+            "raw",
+            Some(("0000:00:02.0", "0x8086", "0x030000")),
+        );
+        add_device(&root, "amdgpu_bl1", "raw", None);
+        add_device(&root, "generic", "unknown", None);
+        add_device(&root, "another", "unknown", None);
+        let bldir = root.join("class/backlight");
+        let names =
+            |bldir| -> Vec<_> { rank(bldir).unwrap().into_iter().map(|c| c.name).collect() };
+
+        // Without boot_vga, the iGPU wins and devices that rank the same are ordered by name
+        assert_eq!(
+            names(&bldir),
+            [
+                "intel_backlight",
+                "nvidia_0",
+                "amdgpu_bl1",
+                "another",
+                "generic"
+            ]
+        );
+
+        fs::write(pci_path(&root, "0000:01:00.0").join("boot_vga"), "1").unwrap();
+        fs::write(pci_path(&root, "0000:00:02.0").join("boot_vga"), "0").unwrap();
+        assert_eq!(
+            names(&bldir),
+            [
+                "nvidia_0",
+                "intel_backlight",
+                "amdgpu_bl1",
+                "another",
+                "generic"
+            ]
+        );
+
+        // The panel is lit through the iGPU's connector, even though the dGPU was the boot display
+        let edp = pci_path(&root, "0000:00:02.0").join("drm/card1/card1-eDP-1");
+        fs::create_dir_all(&edp).unwrap();
+        fs::write(edp.join("status"), "connected").unwrap();
+        fs::write(edp.join("enabled"), "enabled").unwrap();
+        assert_eq!(
+            names(&bldir),
+            [
+                "intel_backlight",
+                "nvidia_0",
+                "amdgpu_bl1",
+                "another",
+                "generic"
+            ]
+        );
+
+        add_device(&root, "acpi_video0", "firmware", None);
+        let best = &rank(&bldir).unwrap()[0];
+        assert_eq!(best.name, "acpi_video0");
+        assert_eq!(best.kind, Some(BacklightType::Firmware));
+    }
+
+    #[test]
+    fn preferring_the_igpu() {
+        let root = Fixture::new("igpu");
+        // AMD dGPUs report the same VGA class as the iGPU, and their backlight sorts first by name
+        add_device(
+            &root,
+            "amdgpu_bl1",
+            "raw",
+            Some(("0000:03:00.0", "0x1002", "0x030000")),
+        );
+        add_device(
+            &root,
+            "intel_backlight",
+            "raw",
+            Some(("0000:00:02.0", "0x8086", "0x030000")),
+        );
+        let ranked = rank(&root.join("class/backlight")).unwrap();
+        assert_eq!(ranked[0].name, "intel_backlight");
+    }
+
+    #[test]
+    fn following_the_mux() {
+        let root = Fixture::new("mux");
+        let (igpu, dgpu) = ("0000:00:02.0", "0000:01:00.0");
+        add_device(
+            &root,
+            "intel_backlight",
+            "raw",
+            Some((igpu, "0x8086", "0x030000")),
+        );
+        add_device(&root, "nvidia_0", "raw", Some((dgpu, "0x10de", "0x030000")));
+        let bldir = root.join("class/backlight");
+        let pci = |slot: &str, attr: &str, value: &str| {
+            fs::write(pci_path(&root, slot).join(attr), value).unwrap();
+        };
+        let best = || rank(&bldir).unwrap().swap_remove(0).name;
+
+        // vga_switcheroo knows the MUX position
+        let switcheroo = root.join(SWITCHEROO);
+        fs::create_dir_all(switcheroo.parent().unwrap()).unwrap();
+        fs::write(
+            &switcheroo,
+            format!("0:IGD:+:Pwr:{igpu}\n1:DIS: :Off:{dgpu}\n"),
+        )
+        .unwrap();
+        assert_eq!(best(), "intel_backlight");
+        fs::write(
+            &switcheroo,
+            format!("0:IGD: :Off:{igpu}\n1:DIS:+:Pwr:{dgpu}\n"),
+        )
+        .unwrap();
+        assert_eq!(best(), "nvidia_0");
+        fs::remove_file(&switcheroo).unwrap();
+
+        // MUX set to the dGPU, the iGPU is powered down
+        pci(igpu, "enable", "0");
+        pci(dgpu, "enable", "1");
+        assert_eq!(best(), "nvidia_0");
+
+        // MUX set to the iGPU, the dGPU is powered down
+        pci(igpu, "enable", "1");
+        pci(dgpu, "enable", "0");
+        assert_eq!(best(), "intel_backlight");
+
+        // Hybrid mode, both GPUs are powered and the one the firmware booted with drives the panel
+        pci(dgpu, "enable", "1");
+        pci(igpu, "boot_vga", "0");
+        pci(dgpu, "boot_vga", "1");
+        assert_eq!(best(), "nvidia_0");
+        pci(igpu, "boot_vga", "1");
+        pci(dgpu, "boot_vga", "0");
+        assert_eq!(best(), "intel_backlight");
+    }
+}
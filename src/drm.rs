@@ -0,0 +1,191 @@
+//! Mapping of backlight devices to the internal panel connector they light up. See [``Device::connector``][crate::Device::connector]
+
+use crate::{attr::read_attr, gpu::Gpu, Device};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+/// Connector types of built-in panels, as used in DRM connector names
+pub(crate) const INTERNAL_TYPES: [&str; 3] = ["eDP", "LVDS", "DSI"];
+
+/// A DRM connector of an internal panel, like `/sys/class/drm/card1-eDP-1`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Connector {
+    card: String,
+    output: String,
+    connected: bool,
+    enabled: bool,
+    path: PathBuf,
+}
+
+impl Connector {
+    /// Reads the connector from its directory, returns None if it isn't the connector of an internal panel
+    pub(crate) fn read(dir: &Path) -> Option<Self> {
+        let name = dir.file_name()?.to_str()?;
+        let (card, output) = name.split_once('-')?;
+        let kind = output.rsplit_once('-')?.0;
+        if !card.starts_with("card") || !INTERNAL_TYPES.contains(&kind) {
+            return None;
+        }
+        Some(Self {
+            card: card.to_owned(),
+            output: output.to_owned(),
+            connected: read_attr(dir, "status").as_deref() == Some("connected"),
+            enabled: read_attr(dir, "enabled").as_deref() == Some("enabled"),
+            path: dir.to_path_buf(),
+        })
+    }
+
+    /// Finds the internal panel connector the backlight device in the given directory belongs to.
+    ///
+    /// Some drivers link the backlight to the connector directly, otherwise the connectors of the device's GPU are searched.
+    /// If the GPU has several internal connectors, the one that is connected and enabled wins.
+    pub(crate) fn find(device_dir: &Path) -> Option<Self> {
+        if let Some(connector) = fs::canonicalize(device_dir.join("device"))
+            .ok()
+            .and_then(|linked| Self::read(&linked))
+        {
+            return Some(connector);
+        }
+        let gpu = Gpu::find(device_dir)?;
+        let mut connectors: Vec<_> = fs::read_dir(gpu.path().join("drm"))
+            .ok()?
+            .filter_map(Result::ok)
+            .filter_map(|card| fs::read_dir(card.path()).ok())
+            .flatten()
+            .filter_map(|entry| Self::read(&entry.ok()?.path()))
+            .collect();
+        connectors.sort_by(|a, b| {
+            b.is_active()
+                .cmp(&a.is_active())
+                .then_with(|| a.name().cmp(&b.name()))
+        });
+        connectors.into_iter().next()
+    }
+
+    /// Returns the DRM card the connector belongs to, for example `card1`
+    #[must_use]
+    pub fn card(&self) -> &str {
+        &self.card
+    }
+
+    /// Returns the name of the output, for example `eDP-1`
+    #[must_use]
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// Returns the full name of the connector, for example `card1-eDP-1`
+    #[must_use]
+    pub fn name(&self) -> String {
+        format!("{}-{}", self.card, self.output)
+    }
+
+    /// Returns true if a panel is attached to the connector, as read from its `status` attribute
+    #[must_use]
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Returns true if the connector is currently driven by its GPU, as read from its `enabled` attribute
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns true if the connector is connected and enabled, meaning that its backlight is the one lighting up the panel
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.connected && self.enabled
+    }
+
+    /// Returns the location of the connector directory
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns true if the given name refers to this connector, either as an output (`eDP-1`) or a connector name (`card1-eDP-1`)
+    pub(crate) fn matches(&self, name: &str) -> bool {
+        self.output == name || self.name() == name
+    }
+}
+
+impl fmt::Display for Connector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match (self.connected, self.enabled) {
+            (true, true) => "connected, enabled",
+            (true, false) => "connected, disabled",
+            (false, _) => "disconnected",
+        };
+        write!(f, "{} ({state})", self.output)
+    }
+}
+
+impl Device {
+    /// Returns the internal panel connector the device belongs to, see [``Connector``].
+    /// Returns None if the device can't be tied to a DRM connector, like most platform backlights.
+    #[must_use]
+    pub fn connector(&self) -> Option<Connector> {
+        Connector::find(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::Fixture;
+    use std::os::unix::fs::symlink;
+
+    fn add_connector(gpu: &Path, name: &str, status: &str, enabled: &str) -> PathBuf {
+        let card = name.split_once('-').unwrap().0;
+        let dir = gpu.join("drm").join(card).join(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("status"), status).unwrap();
+        fs::write(dir.join("enabled"), enabled).unwrap();
+        fs::canonicalize(dir).unwrap()
+    }
+
+    #[test]
+    fn finding_connectors() {
+        let root = Fixture::new("drm");
+        let gpu = root.join("devices/pci0000:00/0000:00:08.1/0000:05:00.0");
+        fs::create_dir_all(&gpu).unwrap();
+        fs::write(gpu.join("vendor"), "0x1002").unwrap();
+        fs::write(gpu.join("class"), "0x030000").unwrap();
+        let edp = add_connector(&gpu, "card0-eDP-1", "connected\n", "enabled\n");
+        add_connector(&gpu, "card0-eDP-2", "disconnected\n", "disabled\n");
+        add_connector(&gpu, "card0-HDMI-A-1", "connected\n", "enabled\n");
+
+        let device = |name: &str, target: &Path| {
+            let path = root.join("class/backlight").join(name);
+            fs::create_dir_all(&path).unwrap();
+            symlink(fs::canonicalize(target).unwrap(), path.join("device")).unwrap();
+            path
+        };
+
+        let connector = Connector::find(&device("intel_backlight", &edp)).unwrap();
+        assert_eq!((connector.card(), connector.output()), ("card0", "eDP-1"));
+        assert!(connector.matches("eDP-1") && connector.matches("card0-eDP-1"));
+
+        let connector = Connector::find(&device("amdgpu_bl0", &gpu)).unwrap();
+        assert_eq!(connector.name(), "card0-eDP-1");
+        assert!(connector.is_active());
+        assert_eq!(connector.to_string(), "eDP-1 (connected, enabled)");
+
+        for name in ["intel_backlight", "amdgpu_bl0"] {
+            let path = root.join("class/backlight").join(name);
+            fs::write(path.join("brightness"), "10").unwrap();
+            fs::write(path.join("max_brightness"), "100").unwrap();
+        }
+        let device = Device::new_in(&root, Some("eDP-1".into())).unwrap();
+        assert_eq!(device.name(), "amdgpu_bl0");
+        assert!(Device::new_in(&root, Some("eDP-2".into())).is_err());
+
+        fs::write(edp.join("enabled"), "disabled").unwrap();
+        let connector = Connector::find(&root.join("class/backlight/amdgpu_bl0")).unwrap();
+        assert_eq!(connector.to_string(), "eDP-1 (connected, disabled)");
+        assert!(Connector::read(&gpu.join("drm/card0/card0-HDMI-A-1")).is_none());
+    }
+}
@@ -1,6 +1,6 @@
-//! All blight library related errors in one place. See [BlibError]
+//! All blight library related errors in one place. See [`BlibError`]
 
-use std::{borrow::Cow, error::Error};
+use std::{borrow::Cow, error::Error, path::PathBuf};
 
 pub type BlResult<T> = Result<T, BlibError>;
 /// All blight library related errors in one place. Every time one of the functions or methods of the library return an error, it'll always be one of this enum's variants.
@@ -9,12 +9,30 @@ pub type BlResult<T> = Result<T, BlibError>;
 #[derive(Debug)]
 pub enum BlibError {
     ReadBlDir(std::io::Error),
+    /// The LED directory (`/sys/class/leds`), which holds keyboard backlights, couldn't be read
+    ReadLedDir(std::io::Error),
+    /// The DRM directory (`/sys/class/drm`), which holds the display outputs of external monitors, couldn't be read
+    ReadDrmDir(std::io::Error),
     NoDeviceFound,
-    WriteNewVal { err: std::io::Error, dev: String },
+    /// Writing to a device failed, `path` is its brightness file
+    WriteNewVal {
+        err: std::io::Error,
+        dev: String,
+        path: PathBuf,
+    },
     ReadMax,
     ReadCurrent,
     SweepError(std::io::Error),
-    ValueTooLarge { given: u32, supported: u32 },
+    ValueTooLarge {
+        given: u32,
+        supported: u32,
+    },
+    /// A DDC/CI monitor couldn't be reached, `bus` is the I2C device node it was reached through, if known
+    DdcError {
+        err: std::io::Error,
+        dev: String,
+        bus: Option<PathBuf>,
+    },
 }
 
 #[doc(hidden)]
@@ -24,13 +42,13 @@ pub trait Tip: Error + 'static {
 
 impl Tip for BlibError {
     fn tip(&self) -> Option<Cow<'static, str>> {
-        use BlibError::WriteNewVal;
+        use BlibError::{DdcError, ReadDrmDir, ReadLedDir, WriteNewVal};
         match &self {
-            WriteNewVal { dev, .. } => {
+            WriteNewVal { path, .. } => {
                 let tip_msg = format!(
-                    "{main} '{dir}/{dev}/brightness'\n{extra}",
+                    "{main} '{path}'\n{extra}",
                     main = "make sure you have write permission to the file",
-                    dir = super::BLDIR,
+                    path = path.display(),
                     extra = "
 Run `sudo blight setup` to install necessarry udev rules and add user to video group.
 or visit https://wiki.archlinux.org/title/Backlight#Hardware_interfaces
@@ -38,6 +56,26 @@ if you'd like to do it manually.",
                 );
                 Some(tip_msg.into())
             }
+            ReadLedDir(_) => Some(
+                "keyboard backlights appear in the LED directory once the driver of the keyboard (like thinkpad_acpi) is loaded"
+                    .into(),
+            ),
+            ReadDrmDir(_) => Some(
+                "display outputs appear in the DRM directory once the driver of the GPU (like i915, amdgpu or nouveau) is loaded"
+                    .into(),
+            ),
+            DdcError { bus, .. } => {
+                let bus = bus
+                    .as_ref()
+                    .map_or_else(|| "/dev/i2c-*".into(), |bus| bus.display().to_string());
+                Some(
+                    format!(
+                        "make sure the i2c-dev module is loaded (sudo modprobe i2c-dev), that you have read and write access to {bus} \
+(usually by joining the i2c group) and that DDC/CI is enabled in the monitor's menu"
+                    )
+                    .into(),
+                )
+            }
             _ => None,
         }
     }
@@ -45,14 +83,21 @@ if you'd like to do it manually.",
 
 impl std::fmt::Display for BlibError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        use BlibError::*;
+        use BlibError::{
+            DdcError, NoDeviceFound, ReadBlDir, ReadCurrent, ReadDrmDir, ReadLedDir, ReadMax,
+            SweepError, ValueTooLarge, WriteNewVal,
+        };
         match self {
-            ReadBlDir(e) => write!(f, "failed to read {} directory\n{e}", super::BLDIR),
+            ReadBlDir(e) => write!(f, "failed to read backlight directory\n{e}"),
+
+            ReadLedDir(e) => write!(f, "failed to read LED directory\n{e}"),
+
+            ReadDrmDir(e) => write!(f, "failed to read DRM directory\n{e}"),
 
             NoDeviceFound => write!(f, "no known backlight device detected"),
 
             WriteNewVal { err, .. } => {
-                write!(f, "failed to write to the brightness file ({err})",)
+                write!(f, "failed to write to the brightness file ({err})")
             }
 
             ReadCurrent => write!(f, "failed to read current brightness value"),
@@ -65,6 +110,10 @@ impl std::fmt::Display for BlibError {
                 f,
                 "provided value ({given}) is larger than the max supported value of {supported}"
             ),
+
+            DdcError { err, dev, .. } => {
+                write!(f, "failed to reach monitor {dev} over DDC/CI ({err})")
+            }
         }
     }
 }
@@ -0,0 +1,54 @@
+//! Throwaway sysfs trees for the tests, see [``Fixture``]
+
+use std::{
+    env, fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// A fake sysfs root in its own directory under the system temp dir. It's removed on drop, so a failing test doesn't leave it behind.
+///
+/// Derefs to the root path, so it can be passed wherever a root is taken.
+pub(crate) struct Fixture {
+    root: PathBuf,
+}
+
+impl Fixture {
+    /// Creates an empty root. The name only has to be unique among the tests, the process id keeps concurrent test runs apart.
+    pub(crate) fn new(name: &str) -> Self {
+        let root = env::temp_dir().join(format!("blight-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).expect("failed to create test fixture");
+        Self { root }
+    }
+
+    /// Adds a device to a class directory (like `class/backlight`) with the given brightness and max brightness, returning its directory
+    pub(crate) fn device(&self, class: &str, name: &str, current: u32, max: u32) -> PathBuf {
+        let dir = self.root.join(class).join(name);
+        fs::create_dir_all(&dir).expect("failed to create test device");
+        fs::write(dir.join("brightness"), format!("{current}\n")).unwrap();
+        fs::write(dir.join("max_brightness"), format!("{max}\n")).unwrap();
+        dir
+    }
+}
+
+impl Deref for Fixture {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl AsRef<Path> for Fixture {
+    fn as_ref(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
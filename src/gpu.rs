@@ -0,0 +1,243 @@
+//! Identification of the GPU a backlight device belongs to. See [``Device::gpu``][crate::Device::gpu]
+
+use crate::{attr::read_attr, Device};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+const PCI_CLASS_DISPLAY: u32 = 0x03;
+
+/// Manufacturer of a GPU, as identified by its PCI vendor id
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Vendor {
+    Intel,
+    Amd,
+    Nvidia,
+    /// Any other vendor, with its PCI vendor id
+    Other(u16),
+}
+
+impl Vendor {
+    /// Returns the vendor matching the given PCI vendor id
+    #[must_use]
+    pub fn from_id(id: u16) -> Self {
+        match id {
+            0x8086 => Self::Intel,
+            0x1002 => Self::Amd,
+            0x10de => Self::Nvidia,
+            id => Self::Other(id),
+        }
+    }
+}
+
+impl fmt::Display for Vendor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Intel => f.write_str("Intel"),
+            Self::Amd => f.write_str("AMD"),
+            Self::Nvidia => f.write_str("NVIDIA"),
+            Self::Other(id) => write!(f, "{id:#06x}"),
+        }
+    }
+}
+
+/// Whether a GPU is built into the CPU or is a separate chip.
+///
+/// Told apart by the PCI topology: an integrated GPU sits directly on the root bus (like `pci0000:00/0000:00:02.0`),
+/// while a discrete one is reached through a PCI bridge (like `pci0000:00/0000:00:01.0/0000:01:00.0`).
+/// AMD APUs are the exception, their GPU sits behind an internal bridge (like `pci0000:00/0000:00:08.1/0000:05:00.0`)
+/// just like a dGPU in a slot. An AMD GPU behind a single bridge is taken for an APU if the firmware booted with it (`boot_vga`),
+/// as laptops boot from the iGPU.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GpuKind {
+    Integrated,
+    Discrete,
+}
+
+impl fmt::Display for GpuKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Integrated => "integrated",
+            Self::Discrete => "discrete",
+        })
+    }
+}
+
+/// The PCI display device a backlight device belongs to.
+///
+/// Found by following the `device` link of the backlight device back to the PCI device, reading its `vendor` and `class` attributes on the way.
+/// Firmware (ACPI) backlights are resolved through the `physical_node` link of their ACPI video device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Gpu {
+    vendor: Vendor,
+    slot: String,
+    kind: GpuKind,
+    path: PathBuf,
+}
+
+impl Gpu {
+    /// Finds the GPU the backlight device in the given directory belongs to
+    pub(crate) fn find(device_dir: &Path) -> Option<Self> {
+        let linked = fs::canonicalize(device_dir.join("device")).ok()?;
+        // ACPI video devices aren't children of the GPU, but link to it
+        let start = fs::canonicalize(linked.join("physical_node")).unwrap_or(linked);
+        start.ancestors().find_map(Self::read)
+    }
+
+    /// Reads the GPU from a PCI device directory, returns None if the directory isn't a PCI display device
+    fn read(dir: &Path) -> Option<Self> {
+        let vendor = parse_hex(&read_attr(dir, "vendor")?)?;
+        let class = parse_hex(&read_attr(dir, "class")?)?;
+        if class >> 16 != PCI_CLASS_DISPLAY {
+            return None;
+        }
+        let vendor = Vendor::from_id(u16::try_from(vendor).ok()?);
+        let apu = vendor == Vendor::Amd
+            && dir.parent().is_some_and(on_root_bus)
+            && read_attr(dir, "boot_vga").as_deref() == Some("1");
+        let kind = if on_root_bus(dir) || apu {
+            GpuKind::Integrated
+        } else {
+            GpuKind::Discrete
+        };
+        Some(Self {
+            vendor,
+            slot: dir.file_name()?.to_string_lossy().into_owned(),
+            kind,
+            path: dir.to_path_buf(),
+        })
+    }
+
+    /// Returns the manufacturer of the GPU
+    #[must_use]
+    pub fn vendor(&self) -> Vendor {
+        self.vendor
+    }
+
+    /// Returns the PCI slot of the GPU, for example `0000:00:02.0`
+    #[must_use]
+    pub fn slot(&self) -> &str {
+        &self.slot
+    }
+
+    /// Returns whether the GPU is integrated or discrete
+    #[must_use]
+    pub fn kind(&self) -> GpuKind {
+        self.kind
+    }
+
+    /// Returns the location of the PCI device directory
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl fmt::Display for Gpu {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} ({})", self.kind, self.vendor, self.slot)
+    }
+}
+
+impl Device {
+    /// Returns the GPU the device belongs to, see [``Gpu``].
+    /// Returns None if the device isn't tied to a PCI display device, like most platform backlights.
+    #[must_use]
+    pub fn gpu(&self) -> Option<Gpu> {
+        Gpu::find(&self.path)
+    }
+}
+
+/// The parent of a device on the root bus is the host bridge directory, pciDDDD:BB
+fn on_root_bus(dir: &Path) -> bool {
+    dir.parent()
+        .and_then(Path::file_name)
+        .is_some_and(|parent| parent.to_string_lossy().starts_with("pci"))
+}
+
+fn parse_hex(value: &str) -> Option<u32> {
+    u32::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::fixture::Fixture;
+    use std::os::unix::fs::symlink;
+
+    fn add_pci(dir: PathBuf, vendor: &str, class: &str) -> PathBuf {
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("vendor"), vendor).unwrap();
+        fs::write(dir.join("class"), class).unwrap();
+        fs::canonicalize(dir).unwrap()
+    }
+
+    /// Location of a PCI device, on the root bus if its slot is on bus 00 and behind a bridge otherwise
+    pub(crate) fn pci_path(root: &Path, slot: &str) -> PathBuf {
+        let host = root.join("devices/pci0000:00");
+        if slot.starts_with("0000:00:") {
+            host.join(slot)
+        } else {
+            host.join("0000:00:01.0").join(slot)
+        }
+    }
+
+    fn link_device(root: &Path, name: &str, target: &Path) -> PathBuf {
+        let path = root.join("class/backlight").join(name);
+        fs::create_dir_all(&path).unwrap();
+        symlink(target, path.join("device")).unwrap();
+        path
+    }
+
+    #[test]
+    fn identifying_gpus() {
+        let root = Fixture::new("gpu");
+        let pci = |slot| pci_path(&root, slot);
+        let intel = add_pci(pci("0000:00:02.0"), "0x8086\n", "0x030000\n");
+        let nvidia = add_pci(pci("0000:01:00.0"), "0x10de\n", "0x030200\n");
+        let amd = add_pci(pci("0000:03:00.0"), "0x1002\n", "0x030000\n");
+        fs::write(amd.join("boot_vga"), "0\n").unwrap();
+        let apu = add_pci(
+            root.join("devices/pci0000:00/0000:00:08.1/0000:05:00.0"),
+            "0x1002\n",
+            "0x030000\n",
+        );
+        fs::write(apu.join("boot_vga"), "1\n").unwrap();
+        let connector = intel.join("drm/card1/card1-eDP-1");
+        fs::create_dir_all(&connector).unwrap();
+        let acpi = root.join("devices/LNXSYSTM:00/LNXVIDEO:00");
+        fs::create_dir_all(&acpi).unwrap();
+        symlink(&nvidia, acpi.join("physical_node")).unwrap();
+        let platform = root.join("devices/platform/dell-laptop");
+        fs::create_dir_all(&platform).unwrap();
+
+        let gpu = Gpu::find(&link_device(&root, "intel_backlight", &connector)).unwrap();
+        assert_eq!(gpu.vendor(), Vendor::Intel);
+        assert_eq!(gpu.slot(), "0000:00:02.0");
+        assert_eq!(gpu.kind(), GpuKind::Integrated);
+
+        let gpu = Gpu::find(&link_device(&root, "nvidia_0", &nvidia)).unwrap();
+        assert_eq!(gpu.vendor(), Vendor::Nvidia);
+        assert_eq!(gpu.kind(), GpuKind::Discrete);
+        assert_eq!(gpu.to_string(), "discrete NVIDIA (0000:01:00.0)");
+
+        // AMD dGPUs usually report themselves as VGA controllers too, only the bridge gives them away
+        let dgpu = Gpu::find(&link_device(&root, "amdgpu_bl1", &amd)).unwrap();
+        assert_eq!(dgpu.kind(), GpuKind::Discrete);
+        // the APU's GPU is behind an internal bridge as well, but the firmware booted with it
+        let igpu = Gpu::find(&link_device(&root, "amdgpu_bl0", &apu)).unwrap();
+        assert_eq!(igpu.kind(), GpuKind::Integrated);
+        assert_eq!(igpu.to_string(), "integrated AMD (0000:05:00.0)");
+
+        let acpi = Gpu::find(&link_device(
+            &root,
+            "acpi_video0",
+            &fs::canonicalize(acpi).unwrap(),
+        ));
+        assert_eq!(acpi, Some(gpu));
+
+        assert!(Gpu::find(&link_device(&root, "dell_backlight", &platform)).is_none());
+        assert!(Gpu::find(&root.join("class/backlight/generic")).is_none());
+    }
+}
@@ -0,0 +1,347 @@
+//! Synchronized control of several backlight devices at once. See [``DeviceGroup``]
+
+use crate::{
+    drm::Connector, err::BlibError, gpu::Gpu, info::devices_in, Backlight, BlResult, Delay, Device,
+    DeviceInfo, Direction, Easing, Value, SYSFS,
+};
+use std::{borrow::Cow, collections::HashSet, path::Path, thread, time::Duration};
+
+/// A set of backlight devices that are changed together, like the panels of a dual-screen laptop.
+///
+/// Changes are expressed relative to each device's own range, so a 5% step moves every device by 5% of its max brightness,
+/// no matter how many levels it has. Sweeps run in parallel and are timed so that all devices finish together.
+/// # Examples
+/// ```ignore
+/// let group = DeviceGroup::all()?;
+/// let values = group.calculate_change(10, Direction::Inc);
+/// group.sweep_write(&values, Delay::default())?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct DeviceGroup {
+    devices: Vec<Device>,
+}
+
+impl DeviceGroup {
+    /// Creates a group out of the given devices
+    #[must_use]
+    pub fn new(devices: Vec<Device>) -> Self {
+        Self { devices }
+    }
+
+    /// Creates a group of every backlight device whose brightness can be read, see [``devices``][crate::devices].
+    ///
+    /// Laptops often expose the same panel through several interfaces (like `acpi_video0` and `intel_backlight`), so only the best ranked device of every panel is taken.
+    /// Devices light up the same panel if they share a connector, a device that isn't tied to a connector (like an ACPI video device)
+    /// is taken for the internal panel of its GPU. Devices that are tied to neither are always taken.
+    /// # Errors
+    /// * [``BlibError::ReadBlDir``]
+    /// * [``BlibError::NoDeviceFound``] - if there are no devices
+    pub fn all() -> BlResult<Self> {
+        Self::all_in(SYSFS)
+    }
+
+    /// Same as [``DeviceGroup::all``], except that devices are looked up under the given sysfs root, see [``Device::new_in``]
+    /// # Errors
+    /// Same as [``DeviceGroup::all``]
+    pub fn all_in(root: impl AsRef<Path>) -> BlResult<Self> {
+        let root = root.as_ref();
+        let infos = devices_in(root)?;
+        let mut panels = HashSet::new();
+        // devices come in rank order, so the first device of every panel is the best one
+        let devices = infos
+            .iter()
+            .filter(|info| panel(&infos, info).is_none_or(|panel| panels.insert(panel)))
+            .map(|info| Device::new_in(root, Some(Cow::from(info.name.as_str()))))
+            .collect::<BlResult<Vec<_>>>()?;
+        if devices.is_empty() {
+            return Err(BlibError::NoDeviceFound);
+        }
+        Ok(Self::new(devices))
+    }
+
+    /// Returns the devices of the group
+    #[must_use]
+    pub fn devices(&self) -> &[Device] {
+        &self.devices
+    }
+
+    /// Returns the devices of the group mutably, for example to set their curves
+    pub fn devices_mut(&mut self) -> &mut [Device] {
+        &mut self.devices
+    }
+
+    /// Reloads the current values of all devices in place, see [``Device::reload``]
+    pub fn reload(&mut self) {
+        self.devices.iter_mut().for_each(Device::reload);
+    }
+
+    /// Calculates the new value of every device for the given percentage step, see [``Backlight::calculate_change``].
+    /// The values are in the order of [``DeviceGroup::devices``].
+    #[must_use]
+    pub fn calculate_change(&self, step_size: u32, dir: Direction) -> Vec<u32> {
+        self.devices
+            .iter()
+            .map(|device| device.calculate_change(step_size, dir))
+            .collect()
+    }
+
+    /// Same as [``DeviceGroup::calculate_change``] for a [Value] step, see [``Backlight::calculate_change_by``]
+    #[must_use]
+    pub fn calculate_change_by(&self, step: Value, dir: Direction) -> Vec<u32> {
+        self.devices
+            .iter()
+            .map(|device| device.calculate_change_by(step, dir))
+            .collect()
+    }
+
+    /// Converts the value to the raw value of every device, see [``Backlight::to_raw``].
+    /// The values are in the order of [``DeviceGroup::devices``].
+    #[must_use]
+    pub fn to_raw(&self, value: impl Into<Value>) -> Vec<u32> {
+        let value = value.into();
+        self.devices
+            .iter()
+            .map(|device| device.to_raw(value))
+            .collect()
+    }
+
+    /// Sets every device to the given percentage of its max brightness, keeping each one above its [floor][Backlight::min]
+    /// # Errors
+    /// * [``BlibError::WriteNewVal``] - for the first device that fails
+    pub fn set_percent(&self, percent: f64) -> BlResult<()> {
+        let values: Vec<u32> = self
+            .devices
+            .iter()
+            .map(|device| device.floor(device.to_raw(Value::Percent(percent))))
+            .collect();
+        self.write_values(&values)
+    }
+
+    /// Writes one value to each device, in the order of [``DeviceGroup::devices``]
+    /// # Errors
+    /// * [``BlibError::ValueTooLarge``]
+    /// * [``BlibError::WriteNewVal``]
+    /// # Panics
+    /// If the number of values differs from the number of devices
+    pub fn write_values(&self, values: &[u32]) -> BlResult<()> {
+        self.check(values)?;
+        self.devices
+            .iter()
+            .zip(values)
+            .try_for_each(|(device, &value)| device.write_value(value))
+    }
+
+    /// Sweeps every device to its value in parallel, in steps of 1% with the given delay in between, like [``Backlight::sweep_write``].
+    ///
+    /// The device with the longest way to go sets the number of steps, the others move in smaller steps so that all of them finish together.
+    /// Targets below the [floor][Backlight::min] of a device are raised to it.
+    /// # Errors
+    /// * [``BlibError::ValueTooLarge``]
+    /// * [``BlibError::SweepError``]
+    /// # Panics
+    /// If the number of values differs from the number of devices
+    pub fn sweep_write(&self, values: &[u32], delay: Delay) -> BlResult<()> {
+        self.check(values)?;
+        let targets: Vec<u32> = self
+            .devices
+            .iter()
+            .zip(values)
+            .map(|(device, &value)| device.floor(value))
+            .collect();
+        let steps = self
+            .devices
+            .iter()
+            .zip(&targets)
+            .map(|(device, &value)| device.current.abs_diff(value).div_ceil(device.sweep_rate()))
+            .max()
+            .unwrap_or_default();
+        let mut writers = self
+            .devices
+            .iter()
+            .map(Device::open_writer)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(BlibError::SweepError)?;
+        let mut written: Vec<u32> = self.devices.iter().map(Device::current).collect();
+        for step in 1..=steps {
+            for (i, device) in self.devices.iter().enumerate() {
+                let value = interpolate(device.current, targets[i], step, steps);
+                if value != written[i] {
+                    writers[i].write(value).map_err(BlibError::SweepError)?;
+                    written[i] = value;
+                }
+            }
+            thread::sleep(*delay);
+        }
+        Ok(())
+    }
+
+    /// Sweeps every device to its value in parallel over the given duration, see [``Backlight::sweep_write_for``]
+    /// # Errors
+    /// * [``BlibError::ValueTooLarge``]
+    /// * [``BlibError::SweepError``] - for the first device that fails
+    /// # Panics
+    /// If the number of values differs from the number of devices
+    pub fn sweep_write_for(
+        &self,
+        values: &[u32],
+        duration: Duration,
+        easing: Easing,
+    ) -> BlResult<()> {
+        self.check(values)?;
+        thread::scope(|scope| {
+            let sweeps: Vec<_> = self
+                .devices
+                .iter()
+                .zip(values)
+                .map(|(device, &value)| {
+                    scope.spawn(move || device.sweep_write_for(value, duration, easing))
+                })
+                .collect();
+            sweeps.into_iter().try_for_each(|sweep| {
+                sweep
+                    .join()
+                    .unwrap_or_else(|err| std::panic::resume_unwind(err))
+            })
+        })
+    }
+
+    /// Validates all values up front, so that an invalid value doesn't leave the group half changed
+    fn check(&self, values: &[u32]) -> BlResult<()> {
+        assert_eq!(
+            values.len(),
+            self.devices.len(),
+            "expected one value per device"
+        );
+        match self
+            .devices
+            .iter()
+            .zip(values)
+            .find(|(device, &value)| value > device.max)
+        {
+            Some((device, &value)) => Err(BlibError::ValueTooLarge {
+                given: value,
+                supported: device.max,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the value after the given step out of all steps on the way from current to target, rounded to the nearest raw value
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn interpolate(current: u32, target: u32, step: u32, steps: u32) -> u32 {
+    let distance = i64::from(target) - i64::from(current);
+    let moved =
+        (distance * i64::from(step) + distance.signum() * i64::from(steps) / 2) / i64::from(steps);
+    (i64::from(current) + moved) as u32
+}
+
+/// Identifies the panel a device lights up by its connector. A device that isn't tied to a connector (like an ACPI video device)
+/// is taken for the first connector of its GPU, which is the internal panel. None if the device is tied to neither.
+fn panel(infos: &[DeviceInfo], info: &DeviceInfo) -> Option<String> {
+    if let Some(connector) = &info.connector {
+        return Some(connector.name());
+    }
+    let gpu = info.gpu.as_ref()?;
+    infos
+        .iter()
+        .filter(|other| other.gpu.as_ref().map(Gpu::slot) == Some(gpu.slot()))
+        .filter_map(|other| other.connector.as_ref().map(Connector::name))
+        .min()
+        .or_else(|| Some(gpu.slot().to_owned()))
+}
+
+impl From<Vec<Device>> for DeviceGroup {
+    fn from(devices: Vec<Device>) -> Self {
+        Self::new(devices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixture::Fixture, gpu::tests::pci_path, BrightnessCurve, BLCLASS};
+    use std::{fs, os::unix::fs::symlink, path::PathBuf, time::Instant};
+
+    #[test]
+    fn changing_devices_together() {
+        let root = Fixture::new("group");
+        root.device(BLCLASS, "panel_a", 50, 100);
+        root.device(BLCLASS, "panel_b", 1000, 4000);
+        let mut group = DeviceGroup::all_in(&root).unwrap();
+        for device in group.devices_mut() {
+            device.set_curve(BrightnessCurve::Linear);
+        }
+        assert_eq!(group.calculate_change(10, Direction::Inc), [60, 1400]);
+
+        group.set_percent(40.0).unwrap();
+        group.reload();
+        let current = |group: &DeviceGroup| -> Vec<_> {
+            group.devices().iter().map(Device::current).collect()
+        };
+        assert_eq!(current(&group), [40, 1600]);
+
+        // 20 steps for panel_a, 20 for panel_b: both take the same time
+        let start = Instant::now();
+        group
+            .sweep_write(&[20, 800], Delay::from_millis(5))
+            .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        let values: Vec<_> = (0..=4).map(|step| interpolate(10, 0, step, 4)).collect();
+        assert_eq!(values, [10, 7, 5, 2, 0]);
+        group.reload();
+        assert_eq!(current(&group), [20, 800]);
+
+        assert!(matches!(
+            group.write_values(&[30, 4001]),
+            Err(BlibError::ValueTooLarge { .. })
+        ));
+        group.reload();
+        assert_eq!(current(&group), [20, 800]);
+        assert!(matches!(
+            DeviceGroup::all_in(root.join("missing")),
+            Err(BlibError::ReadBlDir(_))
+        ));
+    }
+
+    #[test]
+    fn one_device_per_panel() {
+        let root = Fixture::new("panels");
+        let gpu = pci_path(&root, "0000:00:02.0");
+        fs::create_dir_all(&gpu).unwrap();
+        fs::write(gpu.join("vendor"), "0x8086").unwrap();
+        fs::write(gpu.join("class"), "0x030000").unwrap();
+        let acpi = root.join("devices/LNXSYSTM:00/LNXVIDEO:00");
+        fs::create_dir_all(&acpi).unwrap();
+        symlink(fs::canonicalize(&gpu).unwrap(), acpi.join("physical_node")).unwrap();
+        let add = |name: &str, kind: &str, target: Option<PathBuf>| {
+            let path = root.join("class/backlight").join(name);
+            fs::create_dir_all(&path).unwrap();
+            fs::write(path.join("brightness"), "50").unwrap();
+            fs::write(path.join("max_brightness"), "100").unwrap();
+            fs::write(path.join("type"), kind).unwrap();
+            if let Some(target) = target {
+                symlink(fs::canonicalize(target).unwrap(), path.join("device")).unwrap();
+            }
+        };
+        let connector = |name: &str| {
+            let dir = gpu.join("drm/card0").join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("status"), "connected").unwrap();
+            fs::write(dir.join("enabled"), "enabled").unwrap();
+            Some(dir)
+        };
+        // acpi_video0 and intel_backlight both drive the internal panel, the second panel of a dual-screen laptop has its own device
+        add("acpi_video0", "firmware", Some(acpi));
+        add("intel_backlight", "raw", connector("card0-eDP-1"));
+        add("card0-eDP-2-backlight", "raw", connector("card0-eDP-2"));
+        add("dell_backlight", "platform", None);
+
+        let group = DeviceGroup::all_in(&root).unwrap();
+        let names: Vec<_> = group.devices().iter().map(Device::name).collect();
+        assert_eq!(
+            names,
+            ["acpi_video0", "dell_backlight", "card0-eDP-2-backlight"]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Enumeration of all backlight devices along with their metadata. See [``devices``]
+
+use crate::{
+    detect, drm::Connector, gpu::Gpu, Backlight, BacklightType, BlResult, Device, BLCLASS, SYSFS,
+};
+use rustix::fs::{access, Access};
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+/// A snapshot of a backlight device and everything blight knows about it, as returned by [``devices``]
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct DeviceInfo {
+    pub name: String,
+    /// Location of the device directory, usually `/sys/class/backlight/{name}`
+    pub path: PathBuf,
+    pub current: u32,
+    pub max: u32,
+    /// Current brightness as a percentage of max brightness
+    pub percent: f64,
+    /// Kind of interface the device is controlled through, None if the device doesn't report it
+    pub kind: Option<BacklightType>,
+    /// Whether the brightness file can be written to by the current user
+    pub writable: bool,
+    pub gpu: Option<Gpu>,
+    /// Whether [``DeviceInfo::gpu``] is the GPU currently driving the display
+    pub active_gpu: bool,
+    pub connector: Option<Connector>,
+    /// Position of the device in the detection order, the device with rank 0 is the one [``Device::new``] picks by default
+    pub rank: usize,
+}
+
+/// Returns every backlight device under [``SYSFS``], in the order detection ranks them (best candidate first).
+///
+/// Devices whose brightness values can't be read are left out, since they can't be controlled either.
+/// # Errors
+/// * [``BlibError::ReadBlDir``][crate::err::BlibError::ReadBlDir]
+pub fn devices() -> BlResult<Vec<DeviceInfo>> {
+    devices_in(SYSFS)
+}
+
+/// Same as [``devices``], except that devices are looked up under the given sysfs root, see [``Device::new_in``]
+/// # Errors
+/// * [``BlibError::ReadBlDir``][crate::err::BlibError::ReadBlDir]
+pub fn devices_in(root: impl AsRef<Path>) -> BlResult<Vec<DeviceInfo>> {
+    let bldir = root.as_ref().join(BLCLASS);
+    let devices = detect::rank(&bldir)?
+        .into_iter()
+        .enumerate()
+        .filter_map(|(rank, candidate)| {
+            let device = Device::load(&bldir, Cow::from(&candidate.name)).ok()?;
+            Some(DeviceInfo {
+                percent: device.current_percent(),
+                writable: device.is_writable(),
+                current: device.current,
+                max: device.max,
+                path: device.path,
+                name: candidate.name,
+                kind: candidate.kind,
+                gpu: candidate.gpu,
+                active_gpu: candidate.active_gpu,
+                connector: candidate.connector,
+                rank,
+            })
+        })
+        .collect();
+    Ok(devices)
+}
+
+impl Device {
+    /// Returns true if the current user is allowed to write to the brightness file of the device
+    #[must_use]
+    pub fn is_writable(&self) -> bool {
+        access(self.path.join("brightness"), Access::WRITE_OK).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{err::BlibError, fixture::Fixture};
+    use std::fs;
+
+    #[test]
+    fn listing_devices() {
+        let root = Fixture::new("info");
+        for (name, kind, current, max) in [
+            ("intel_backlight", "raw", "96000", "192000"),
+            ("acpi_video0", "firmware", "5", "10"),
+            ("broken", "platform", "oops", "10"),
+        ] {
+            let path = root.join("class/backlight").join(name);
+            fs::create_dir_all(&path).unwrap();
+            fs::write(path.join("type"), kind).unwrap();
+            fs::write(path.join("brightness"), current).unwrap();
+            fs::write(path.join("max_brightness"), max).unwrap();
+        }
+
+        let devices = devices_in(&root).unwrap();
+        assert_eq!(devices.len(), 2);
+        let (acpi, intel) = (&devices[0], &devices[1]);
+        assert_eq!((acpi.name.as_str(), acpi.rank), ("acpi_video0", 0));
+        assert_eq!(acpi.kind, Some(BacklightType::Firmware));
+        assert_eq!((acpi.current, acpi.max), (5, 10));
+        assert!(acpi.writable);
+        // the broken platform device still takes up its place in the detection order
+        assert_eq!((intel.name.as_str(), intel.rank), ("intel_backlight", 2));
+        assert!((intel.percent - 50.0).abs() < f64::EPSILON);
+        assert_eq!(intel.path, root.join("class/backlight/intel_backlight"));
+        assert!(intel.gpu.is_none() && intel.connector.is_none());
+
+        assert!(matches!(
+            devices_in(root.join("missing")),
+            Err(BlibError::ReadBlDir(_))
+        ));
+    }
+}
@@ -0,0 +1,125 @@
+//! Keyboard backlights and other LEDs under `/sys/class/leds`, which share the `brightness`/`max_brightness` layout of backlight devices.
+//! See [``Device::keyboard``][crate::Device::keyboard]
+
+use crate::{err::BlibError, BlResult, Device, SYSFS};
+use std::{borrow::Cow, fs, path::Path};
+
+/// Location of LED devices, relative to the sysfs root
+const LEDCLASS: &str = "class/leds";
+/// Keyboard backlights are named `{driver}::kbd_backlight`, like `tpacpi::kbd_backlight`
+const KBD_FUNCTION: &str = "kbd_backlight";
+
+impl Device {
+    /// Creates a [Device] instance for a keyboard backlight, read from `/sys/class/leds/`.
+    ///
+    /// Unless a name is given, the first keyboard backlight (by name) is picked. Any other LED can be opened by passing its name.
+    /// The returned device works like a backlight device, so [``Backlight::calculate_change``][crate::Backlight::calculate_change] and [``Backlight::sweep_write``][crate::Backlight::sweep_write] can be used as usual.
+    /// # Examples
+    /// ```ignore
+    /// let kbd = Device::keyboard(None)?;
+    /// kbd.write_value(kbd.calculate_change(1, Direction::Inc))?;
+    /// ```
+    /// # Errors
+    /// * [``BlibError::ReadLedDir``] - if the LED directory can't be read
+    /// * Same as [``Device::new``] otherwise
+    pub fn keyboard(name: Option<Cow<str>>) -> BlResult<Device> {
+        Self::keyboard_in(SYSFS, name)
+    }
+
+    /// Same as [``Device::keyboard``], except that devices are looked up under the given sysfs root, see [``Device::new_in``]
+    /// # Errors
+    /// Same as [``Device::keyboard``]
+    pub fn keyboard_in(root: impl AsRef<Path>, name: Option<Cow<str>>) -> BlResult<Device> {
+        let leddir = root.as_ref().join(LEDCLASS);
+        let name = match name {
+            Some(n) if leddir.join(n.as_ref()).join("brightness").is_file() => n,
+            Some(_) => return Err(BlibError::NoDeviceFound),
+            None => Cow::from(
+                keyboard_names(&leddir)?
+                    .into_iter()
+                    .next()
+                    .ok_or(BlibError::NoDeviceFound)?,
+            ),
+        };
+        Self::load(&leddir, name)
+    }
+}
+
+/// Returns every keyboard backlight under [``SYSFS``] whose brightness can be read, ordered by name
+/// # Errors
+/// * [``BlibError::ReadLedDir``]
+pub fn keyboards() -> BlResult<Vec<Device>> {
+    keyboards_in(SYSFS)
+}
+
+/// Same as [``keyboards``], except that devices are looked up under the given sysfs root, see [``Device::new_in``]
+/// # Errors
+/// * [``BlibError::ReadLedDir``]
+pub fn keyboards_in(root: impl AsRef<Path>) -> BlResult<Vec<Device>> {
+    let leddir = root.as_ref().join(LEDCLASS);
+    Ok(keyboard_names(&leddir)?
+        .into_iter()
+        .filter_map(|name| Device::load(&leddir, Cow::from(name)).ok())
+        .collect())
+}
+
+fn keyboard_names(leddir: &Path) -> BlResult<Vec<String>> {
+    let mut names: Vec<_> = fs::read_dir(leddir)
+        .map_err(BlibError::ReadLedDir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| {
+            name.rsplit("::")
+                .next()
+                .is_some_and(|f| f.starts_with(KBD_FUNCTION))
+        })
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixture::Fixture, Direction};
+
+    #[test]
+    fn keyboard_backlights() {
+        let root = Fixture::new("leds");
+        for name in [
+            "tpacpi::kbd_backlight",
+            "input3::capslock",
+            "asus::kbd_backlight",
+        ] {
+            root.device(LEDCLASS, name, 1, 3);
+        }
+
+        let kbd = Device::keyboard_in(&root, None).unwrap();
+        assert_eq!(kbd.name(), "asus::kbd_backlight");
+        assert_eq!(kbd.calculate_change(1, Direction::Inc), 2);
+        kbd.sweep_write(3, crate::Delay::from_millis(1)).unwrap();
+        let names: Vec<_> = keyboards_in(&root)
+            .unwrap()
+            .into_iter()
+            .map(|kbd| (kbd.name().to_owned(), kbd.current()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("asus::kbd_backlight".to_owned(), 3),
+                ("tpacpi::kbd_backlight".to_owned(), 1)
+            ]
+        );
+
+        let capslock = Device::keyboard_in(&root, Some("input3::capslock".into())).unwrap();
+        assert_eq!(capslock.max(), 3);
+        assert!(matches!(
+            Device::keyboard_in(&root, Some("dell::kbd_backlight".into())),
+            Err(BlibError::NoDeviceFound)
+        ));
+        assert!(matches!(
+            keyboards_in(root.join("missing")),
+            Err(BlibError::ReadLedDir(_))
+        ));
+    }
+}
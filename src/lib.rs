@@ -6,35 +6,48 @@
 //! This documentation only covers the library aspect, for CLI related docs, visit the project's [Github repo](https://github.com/voltaireNoir/blight).
 //!
 //! Two features of blight that standout:
-//! 1. Prioritizing device detection in this order: iGPU>dGPU>ACPI>Fallback device.
+//! 1. Picking the right device on its own, by ranking devices on the kind of interface the kernel reports for them (firmware > platform > raw).
 //! 2. Smooth backlight change by writing in increments/decrements of 1 with a few milliseconds of delay. \
 //! > **IMPORTANT:** You need write permission for the file `/sys/class/backlight/{your_device}/brightness` to change brightness.
 //! > The CLI utility comes with a helper script that let's you gain access to the brightness file (which may not always work), which you can run by using the command `sudo blight setup`.
 //! > If you're only using blight as a dependency, you can read about gaining file permissions [here](https://wiki.archlinux.org/title/Backlight#ACPI).
+//! > With the default `logind` feature, writes fall back to systemd-logind when the file isn't writable, which works without any permission changes for the active session.
 //!
 //! # Usage
 //! ```ignore
-//! use blight::{BlResult, Change, Device, Direction, Delay};
+//! use blight::{Backlight, BlResult, Change, Device, Direction, Delay};
 //!
 //! fn main() -> BlResult<()> {
 //!     // Using the helper functions
 //!     blight::change_bl(5, Change::Regular, Direction::Inc, None)?; // Increases brightness by 5%
 //!     blight::set_bl(50, Some("nvidia_0".into()))?; // Sets brightness value (not percentage) to 50
 //!
-//!     // Doing it manually
+//!     // Doing it manually (the change, sweep and percentage methods come from the Backlight trait)
 //!     let mut dev = Device::new(None)?;
 //!     let new = dev.calculate_change(5, Direction::Dec); // safely calculate value to write
 //!     dev.write_value(new)?; // decreases brightness by 5%
 //!     dev.reload(); // reloads current brightness value (important)
 //!     let new = dev.calculate_change(5, Direction::Inc);
 //!     dev.sweep_write(new, Delay::default()); // smoothly increases brightness by 5%
+//!     dev.set_percent(40.0)?; // sets brightness to 40% of max
+//!
+//!     // Listing all devices, best detection candidate first
+//!     for info in blight::devices()? {
+//!         println!("{} {:.0}% (rank {})", info.name, info.percent, info.rank);
+//!     }
 //!     Ok(())
 //! }
 //! ```
+//!
+//! # Features
+//! * `tokio` - provides [``AsyncDevice``], an async variant of [``Device``] that doesn't block the tokio runtime
+//! * `logind` (default) - writes brightness values through systemd-logind when the brightness file isn't writable by the current user, see [``logind``]
+//! * `testing` - provides [``MockDevice``], an in-memory [``Backlight``] that records its writes, for testing code that uses blight without root or real hardware
 
 #[cfg(not(target_os = "linux"))]
 compile_error!("blight is only supported on linux");
 
+use backlight::check_max;
 use err::BlibError;
 use std::{
     borrow::Cow,
@@ -42,17 +55,57 @@ use std::{
     io::prelude::*,
     ops::Deref,
     path::{Path, PathBuf},
-    thread,
     time::Duration,
 };
 
+#[cfg(feature = "tokio")]
+pub mod async_device;
+pub mod attr;
+pub mod backlight;
+pub mod curve;
+pub mod ddc;
+mod detect;
+pub mod drm;
 pub mod err;
+#[cfg(test)]
+mod fixture;
+pub mod gpu;
+pub mod group;
+pub mod info;
+pub mod led;
+#[cfg(feature = "logind")]
+pub mod logind;
+#[cfg(feature = "testing")]
+pub mod mock;
+pub mod sweep;
+pub mod watch;
+#[cfg(feature = "tokio")]
+pub use async_device::AsyncDevice;
+pub use attr::{BacklightType, BlPower};
+pub use backlight::Backlight;
+pub use curve::{BrightnessCurve, Scale};
+pub use ddc::{ddc_outputs, ddc_outputs_in, DdcOutput, Monitor};
+pub use drm::Connector;
 pub use err::BlResult;
+pub use gpu::{Gpu, GpuKind, Vendor};
+pub use group::DeviceGroup;
+pub use info::{devices, devices_in, DeviceInfo};
+pub use led::{keyboards, keyboards_in};
+#[cfg(feature = "testing")]
+pub use mock::MockDevice;
+pub use sweep::{Easing, SweepHandle};
+pub use watch::{BrightnessAttr, BrightnessEvent, Watcher};
+
+/// Linux sysfs mount point. This is the default root used by [``Device::new``], see [``Device::new_in``] for using a different one.
+pub const SYSFS: &str = "/sys";
 
 /// Linux backlight directory location. All backlight hardware devices appear here.
 pub const BLDIR: &str = "/sys/class/backlight";
 
-/// This enum is used to specify the direction in which the backlight should be changed in the [``change_bl``] and [``Device::calculate_change``] functions.
+/// Location of the backlight class directory relative to the sysfs root.
+const BLCLASS: &str = "class/backlight";
+
+/// This enum is used to specify the direction in which the backlight should be changed in the [``change_bl``] and [``Backlight::calculate_change``] functions.
 /// Inc -> Increase, Dec -> Decrease.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Direction {
@@ -70,7 +123,27 @@ pub enum Change {
     Sweep,
 }
 
-/// A wrapper type for [``std::time::Duration``] used for specifying delay between each iteration of the loop in [``Device::sweep_write``].
+/// A brightness value, expressed either in raw units of the device or as a percentage of its max brightness.
+///
+/// Raw values are written to the brightness file as they are, whereas percentages are converted using [``Backlight::to_raw``],
+/// which rounds to the nearest raw value. This makes the conversion symmetric with [``Backlight::to_percent``],
+/// meaning that a percentage read from a device always converts back to the same raw value.
+///
+/// A plain [u32] converts into [``Value::Raw``], so functions taking `impl Into<Value>` accept raw values directly.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Value {
+    Raw(u32),
+    Percent(f64),
+}
+
+impl From<u32> for Value {
+    fn from(value: u32) -> Self {
+        Self::Raw(value)
+    }
+}
+
+/// A wrapper type for [``std::time::Duration``] used for specifying delay between each iteration of the loop in [``Backlight::sweep_write``].
+/// For sweeps that take a fixed amount of time, see [``Backlight::sweep_write_for``].
 ///
 /// Delay implements the Default trait, which always returns a Delay of 25ms (recommended delay for smooth brightness transisions).
 /// The struct also provides the [``from_millis``][Delay::from_millis] constructor, if you'd like to set your own duration in milliseconds.
@@ -98,6 +171,7 @@ impl Default for Delay {
 }
 
 impl Delay {
+    #[must_use]
     pub fn from_millis(millis: u64) -> Self {
         Self(Duration::from_millis(millis))
     }
@@ -108,7 +182,9 @@ impl Delay {
 /// A Device instance is created by using the [constructor][Device::new], values are read from /sys/class/backlight/ directory based on the detected GPU device.
 /// The constructor uses the default detection method unless a device name is passed as an argument. Based on whether a device is detected, the constructor will either return Some(Device) or None,
 /// if no device is detected. \
-/// This is how the devices are priorirized: ``AmdGPU or Intel > Nvdia > ACPI > Any Fallback Device``, unless a device name is passed as an argument.
+/// Devices are prioritized by their kernel reported type: ``firmware > platform > raw > untyped``, preferring raw devices of the GPU driving the display
+/// and then those of integrated GPUs (identified through PCI vendor ids, see [``Device::gpu``]),
+/// unless a device name is passed as an argument. Devices that rank the same are picked by name, so detection always gives the same result.
 /// # Examples
 /// ```ignore
 /// let bl = Device::new(None)?;
@@ -119,13 +195,19 @@ pub struct Device {
     name: String,
     current: u32,
     max: u32,
-    device_dir: String,
+    path: PathBuf,
+    curve: BrightnessCurve,
+    min: u32,
+    /// Set with [``Device::set_session``], the session on the system bus is used otherwise
+    #[cfg(feature = "logind")]
+    session: Option<logind::Session>,
 }
 
 impl Device {
     /// Constructor for creating a [Device] instance.
     ///
     /// By default, it uses the priority detection method unless ``Some(device_name)`` is passed as an argument, then that name will be used to create an instance of that device if it exists.
+    /// The name can also be a display output like `eDP-1` (or a connector like `card1-eDP-1`), then the device lighting up that panel is used, see [``Device::connector``].
     /// # Errors
     /// Possible errors that can result from this function include:
     /// * [``BlibError::NoDeviceFound``]
@@ -133,94 +215,160 @@ impl Device {
     /// * [``BlibError::ReadCurrent``]
     /// * [``BlibError::ReadMax``]
     pub fn new(name: Option<Cow<str>>) -> BlResult<Device> {
+        Self::new_in(SYSFS, name)
+    }
+
+    /// Same as [``Device::new``], except that devices are looked up under the given sysfs root instead of [``SYSFS``].
+    ///
+    /// The root is expected to mirror the layout of `/sys`, meaning that backlight devices are read from `{root}/class/backlight/`.
+    /// This is mostly useful for pointing blight at a fixture directory in tests or containers.
+    /// # Examples
+    /// ```ignore
+    /// let bl = Device::new_in("/tmp/fakesys", Some("intel_backlight".into()))?;
+    /// ```
+    /// # Errors
+    /// Same as [``Device::new``]
+    pub fn new_in(root: impl AsRef<Path>, name: Option<Cow<str>>) -> BlResult<Device> {
+        let bldir = root.as_ref().join(BLCLASS);
         let name = if let Some(n) = name {
-            PathBuf::from(format!("{BLDIR}/{n}/brightness"))
-                .is_file()
-                .then_some(n)
-                .ok_or(BlibError::NoDeviceFound)?
+            if bldir.join(n.as_ref()).join("brightness").is_file() {
+                n
+            } else {
+                Cow::from(Self::find_output(&bldir, &n)?)
+            }
         } else {
-            Cow::from(Self::detect_device(BLDIR)?)
+            Cow::from(Self::detect_device(&bldir)?)
         };
-        let device = Self::load(name)?;
+        let device = Self::load(&bldir, name)?;
         Ok(device)
     }
 
-    /// Returns the name of the current device
+    /// Sets the curve used by [``Backlight::calculate_change``]. Unless changed, this is the default for the device's `scale`, see [``BrightnessCurve::for_scale``].
+    pub fn set_curve(&mut self, curve: BrightnessCurve) {
+        self.curve = curve;
+    }
+
+    /// Sets the floor that changes don't go below, see [``Backlight::min``]. It's 0 unless changed.
+    pub fn set_min(&mut self, min: u32) {
+        self.min = min;
+    }
+
+    fn load(bldir: &Path, name: Cow<str>) -> BlResult<Device> {
+        let path = bldir.join(name.as_ref());
+        Ok(Device {
+            current: Self::get_current(&path)?,
+            max: Self::get_max(&path)?,
+            curve: BrightnessCurve::for_scale(Scale::read(&path)),
+            min: 0,
+            #[cfg(feature = "logind")]
+            session: None,
+            path,
+            name: name.into(),
+        })
+    }
+
+    /// Returns the name of the device, same as [``Backlight::name``] but usable without importing the trait
+    #[must_use]
     pub fn name(&self) -> &str {
         &self.name
     }
 
-    /// Returns the current brightness value of the current device
+    /// Returns the current brightness value, same as [``Backlight::current``] but usable without importing the trait
+    #[must_use]
     pub fn current(&self) -> u32 {
         self.current
     }
 
-    /// Returns the max brightness value of the current device
+    /// Returns the max brightness value, same as [``Backlight::max``] but usable without importing the trait
+    #[must_use]
     pub fn max(&self) -> u32 {
         self.max
     }
 
-    fn load(name: Cow<str>) -> BlResult<Device> {
-        let device_dir = format!("{BLDIR}/{name}");
-        Ok(Device {
-            current: Self::get_current(&device_dir)?,
-            max: Self::get_max(&device_dir)?,
-            device_dir,
-            name: name.into(),
-        })
+    /// Same as [``Backlight::calculate_change``], usable without importing the trait
+    #[must_use]
+    pub fn calculate_change(&self, step_size: u32, dir: Direction) -> u32 {
+        Backlight::calculate_change(self, step_size, dir)
     }
 
-    pub fn device_path(&self) -> &Path {
-        self.device_dir.as_ref()
+    /// Same as [``Backlight::sweep_write``], usable without importing the trait
+    /// # Errors
+    /// * [``BlibError::SweepError``]
+    pub fn sweep_write(&self, value: u32, delay: Delay) -> BlResult<()> {
+        Backlight::sweep_write(self, value, delay)
     }
 
-    fn detect_device(bldir: &str) -> BlResult<String> {
-        let dirs: Vec<_> = fs::read_dir(bldir)
-            .map_err(BlibError::ReadBlDir)?
-            .filter_map(|d| d.ok().map(|d| d.file_name()))
-            .collect();
+    /// Returns the location of the device directory, usually `/sys/class/backlight/{name}`
+    #[must_use]
+    pub fn device_path(&self) -> &Path {
+        &self.path
+    }
 
-        let (mut nv, mut ac): (Option<usize>, Option<usize>) = (None, None);
+    fn detect_device(bldir: &Path) -> BlResult<String> {
+        detect::rank(bldir)?
+            .into_iter()
+            .next()
+            .map(|candidate| candidate.name)
+            .ok_or(BlibError::NoDeviceFound)
+    }
 
-        for (i, entry) in dirs.iter().enumerate() {
-            let name = entry.to_string_lossy();
-            if name.contains("amd") || name.contains("intel") {
-                return Ok(name.into_owned());
-            } else if nv.is_none() && (name.contains("nvidia") | name.contains("nv")) {
-                nv = Some(i);
-            } else if ac.is_none() && name.contains("acpi") {
-                ac = Some(i);
-            }
-        }
+    fn find_output(bldir: &Path, output: &str) -> BlResult<String> {
+        detect::rank(bldir)?
+            .into_iter()
+            .find(|candidate| {
+                candidate
+                    .connector
+                    .as_ref()
+                    .is_some_and(|connector| connector.matches(output))
+            })
+            .map(|candidate| candidate.name)
+            .ok_or(BlibError::NoDeviceFound)
+    }
 
-        let to_str = |i: usize| Ok(dirs[i].to_string_lossy().into_owned());
+    /// Step size of step based sweeps: 1% of max, but at least one raw unit
+    pub(crate) fn sweep_rate(&self) -> u32 {
+        backlight::sweep_rate(self.max)
+    }
 
-        if let Some(nv) = nv {
-            to_str(nv)
-        } else if let Some(ac) = ac {
-            to_str(ac)
-        } else if !dirs.is_empty() {
-            to_str(0)
-        } else {
-            Err(BlibError::NoDeviceFound)
+    fn open_bl_file(&self) -> Result<File, std::io::Error> {
+        fs::File::options()
+            .write(true)
+            .open(self.path.join("brightness"))
+    }
+
+    /// Opens the brightness file for writing, falling back to the logind session if the file isn't writable by the current user
+    pub(crate) fn open_writer(&self) -> Result<Writer, std::io::Error> {
+        match self.open_bl_file() {
+            Ok(file) => Ok(Writer::File(file)),
+            #[cfg(feature = "logind")]
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => self
+                .session
+                .clone()
+                .or_else(logind::Session::system)
+                .map(|session| Writer::Logind(session, self.subsystem(), self.name.clone()))
+                .ok_or(err),
+            Err(err) => Err(err),
         }
     }
 
-    fn open_bl_file(&self) -> Result<File, std::io::Error> {
-        let mut path = self.device_path().to_path_buf();
-        path.push("brightness");
-        fs::File::options().write(true).open(path)
+    /// Wraps a failed write to the device, pointing at its brightness file
+    pub(crate) fn write_error(&self, err: std::io::Error) -> BlibError {
+        BlibError::WriteNewVal {
+            err,
+            dev: self.name.clone(),
+            path: self.path.join("brightness"),
+        }
     }
 
     /// Reloads current value for the current device in place.
     /// # Panics
     /// The method panics if the current value fails to be read from the filesystem.
     pub fn reload(&mut self) {
-        self.current = Device::get_current(&self.device_dir).unwrap();
+        self.current = Device::get_current(&self.path).unwrap();
     }
 
-    fn get_max(device_dir: &str) -> BlResult<u32> {
-        let max: u32 = fs::read_to_string(format!("{device_dir}/max_brightness"))
+    fn get_max(device_dir: &Path) -> BlResult<u32> {
+        let max: u32 = fs::read_to_string(device_dir.join("max_brightness"))
             .or(Err(BlibError::ReadMax))?
             .trim()
             .parse()
@@ -228,8 +376,8 @@ impl Device {
         Ok(max)
     }
 
-    fn get_current(device_dir: &str) -> BlResult<u32> {
-        let current: u32 = fs::read_to_string(format!("{device_dir}/brightness"))
+    pub(crate) fn get_current(device_dir: &Path) -> BlResult<u32> {
+        let current: u32 = fs::read_to_string(device_dir.join("brightness"))
             .or(Err(BlibError::ReadCurrent))?
             .trim()
             .parse()
@@ -237,31 +385,60 @@ impl Device {
         Ok(current)
     }
     /// Writes to the brightness file containted in /sys/class/backlight/ dir of the respective detected device, which will result in change of brightness if successful and if the chosen device is the correct one.
+    ///
+    /// With the `logind` feature, the value is handed to systemd-logind instead if the current user isn't allowed to write to the brightness file, see [``logind``].
     /// # Errors
     /// - [``BlibError::WriteNewVal``] - on write failure
     pub fn write_value(&self, value: u32) -> BlResult<()> {
-        if value > self.max {
-            return Err(BlibError::ValueTooLarge {
-                given: value,
-                supported: self.max,
-            });
-        }
-        let convert = |err| BlibError::WriteNewVal {
-            err,
-            dev: self.name.clone(),
-        };
-        write!(self.open_bl_file().map_err(convert)?, "{value}").map_err(convert)?;
+        check_max(value, self.max)?;
+        let convert = |err| self.write_error(err);
+        self.open_writer()
+            .map_err(convert)?
+            .write(value)
+            .map_err(convert)?;
         Ok(())
     }
+}
+
+impl Backlight for Device {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn current(&self) -> u32 {
+        self.current
+    }
+
+    fn max(&self) -> u32 {
+        self.max
+    }
+
+    /// Same as [``Device::write_value``]
+    fn write_raw(&self, value: u32) -> BlResult<()> {
+        self.write_value(value)
+    }
+
+    /// Returns the curve set with [``Device::set_curve``], which defaults to the one for the device's `scale`, see [``BrightnessCurve::for_scale``]
+    fn curve(&self) -> BrightnessCurve {
+        self.curve
+    }
+
+    /// Returns the floor set with [``Device::set_min``]
+    fn min(&self) -> u32 {
+        self.min
+    }
 
     /// Writes to the brightness file starting from the current value in a loop, increasing 1% on each iteration with some delay until target value is reached,
-    /// creating a smooth brightness transition.
+    /// creating a smooth brightness transition. The brightness file is kept open for the whole sweep.
     ///
-    /// This method takes a target value, which can be computed with the help of [``Device::calculate_change``] or can also be manually entered.
+    /// On devices with less than 100 brightness levels, each iteration moves by a single raw unit instead, so the sweep always makes progress.
+    ///
+    /// This method takes a target value, which can be computed with the help of [``Backlight::calculate_change``] or can also be manually entered.
     /// The delay between each iteration of the loop can be set using the [``Delay``] type, or the default can be used by calling [``Delay::default()``],
     /// which sets the delay of 25ms/iter (recommended).
     ///
     /// Note: Nothing is written to the brightness file if the provided value is the same as current brightness value or is larger than the max brightness value.
+    /// Values below the [floor][Backlight::min] are raised to it, see [``Backlight::floor``].
     /// # Example
     /// ```ignore
     /// Device::new(None)?
@@ -270,69 +447,61 @@ impl Device {
     /// # Errors
     /// Possible errors that can result from this function include:
     /// * [``BlibError::SweepError``]
-    pub fn sweep_write(&self, value: u32, delay: Delay) -> Result<(), BlibError> {
-        let mut bfile = self.open_bl_file().map_err(BlibError::SweepError)?;
-        let mut rate = (f64::from(self.max) * 0.01) as u32;
-        let mut current = self.current;
-        let dir = if value > self.current {
-            Direction::Inc
-        } else {
-            Direction::Dec
-        };
+    fn sweep_write(&self, value: u32, delay: Delay) -> BlResult<()> {
+        let mut writer = self.open_writer().map_err(BlibError::SweepError)?;
+        backlight::step_sweep(self.current, self.floor(value), self.max, delay, |v| {
+            writer.write(v).map_err(BlibError::SweepError)
+        })
+    }
 
-        while !(current == value
-            || value > self.max
-            || (current == 0 && dir == Direction::Dec)
-            || (current == self.max && dir == Direction::Inc))
-        {
-            match dir {
-                Direction::Inc => {
-                    if (current + rate) > value {
-                        rate = value - current;
-                    }
-                    current += rate;
-                }
-                Direction::Dec => {
-                    if rate > current {
-                        rate = current;
-                    } else if (current - rate) < value {
-                        rate = current - value;
-                    }
-                    current -= rate;
-                }
-            }
-            bfile.rewind().map_err(BlibError::SweepError)?;
-            write!(bfile, "{current}").map_err(BlibError::SweepError)?;
-            thread::sleep(*delay);
+    /// Same as [``Backlight::sweep_write_for``], with the brightness file kept open for the whole sweep
+    /// # Errors
+    /// * [``BlibError::ValueTooLarge``]
+    /// * [``BlibError::SweepError``]
+    fn sweep_write_for(&self, value: u32, duration: Duration, easing: Easing) -> BlResult<()> {
+        check_max(value, self.max)?;
+        let value = self.floor(value);
+        if value == self.current {
+            return Ok(());
         }
-        Ok(())
+        let mut writer = self.open_writer().map_err(BlibError::SweepError)?;
+        sweep::timed_sweep(self, value, duration, easing, |v| {
+            writer.write(v).map_err(BlibError::SweepError)
+        })
     }
+}
 
-    /// Calculates the new value to be written to the brightness file based on the provided step-size (percentage) and direction,
-    /// using the current and max values of the detected GPU device. (Always guaranteed to be valid)
-    ///
-    /// For example, if the currecnt value is 10 and max is 100, and you want to increase it by 10% (step_size),
-    /// the method will return 20, which can be directly written to the device.
-    ///
-    pub fn calculate_change(&self, step_size: u32, dir: Direction) -> u32 {
-        let step: u32 = (self.max as f32 * (step_size as f32 / 100.0)) as u32;
-        let change: u32 = match dir {
-            Direction::Inc => self.current.saturating_add(step),
-            Direction::Dec => self.current.saturating_sub(step),
-        };
+/// Where the brightness values of a device are written to, see [``Device::open_writer``]
+pub(crate) enum Writer {
+    File(File),
+    /// The session, the subsystem and the name of the device
+    #[cfg(feature = "logind")]
+    Logind(logind::Session, &'static str, String),
+}
 
-        if change > self.max {
-            self.max
-        } else {
-            change
+impl Writer {
+    /// Writes the value, replacing the contents of the brightness file.
+    /// Truncating is a no-op on sysfs, but keeps regular files (like fixtures) from ending up with leftover digits.
+    pub(crate) fn write(&mut self, value: u32) -> std::io::Result<()> {
+        match self {
+            Writer::File(file) => {
+                file.set_len(0)?;
+                file.rewind()?;
+                write!(file, "{value}")
+            }
+            #[cfg(feature = "logind")]
+            Writer::Logind(session, subsystem, name) => {
+                session.set_brightness(subsystem, name, value)
+            }
         }
     }
 }
 
 /// A helper function to change backlight based on step-size (percentage), [Change] type and [Direction].
 ///
-/// Regular change uses [calculated change][Device::calculate_change] value based on step size and is applied instantly.
-/// Sweep change on the other hand, occurs gradually, producing a fade or sweeping effect. (For more info, read about [``Device::sweep_write``])
+/// Regular change uses [calculated change][Backlight::calculate_change] value based on step size and is applied instantly.
+/// The step follows the device's default [curve][Backlight::curve].
+/// Sweep change on the other hand, occurs gradually, producing a fade or sweeping effect. (For more info, read about [``Backlight::sweep_write``])
 /// > Note: No change is applied if the final calculated value is the same as current brightness value
 /// # Errors
 /// Possible errors that can result from this function include:
@@ -344,22 +513,15 @@ pub fn change_bl(
     dir: Direction,
     device_name: Option<Cow<str>>,
 ) -> Result<(), BlibError> {
-    let device = Device::new(device_name)?;
-
-    let change = device.calculate_change(step_size, dir);
-    if change != device.current {
-        match ch {
-            Change::Sweep => device.sweep_write(change, Delay::default())?,
-            Change::Regular => device.write_value(change)?,
-        }
-    }
-    Ok(())
+    Device::new(device_name)?.change(step_size, ch, dir)
 }
 
 /// A helper function which takes a brightness value and writes the value to the brightness file
 /// as long as the given value falls under the min and max bounds of the detected backlight device and is different from the current value.
 ///
-/// *Note: Unlike [change_bl], this function does not calculate any change, it writes the given value directly.*
+/// The value can either be a raw value or a percentage, see [Value].
+///
+/// *Note: Unlike [``change_bl``], this function does not calculate any change, it writes the given value directly.*
 /// # Examples
 /// ```ignore
 /// blight::set_bl(15, None)?;
@@ -367,13 +529,17 @@ pub fn change_bl(
 /// ```ignore
 /// blight::set_bl(50, Some("nvidia_0".into()))?;
 /// ````
+/// ```ignore
+/// blight::set_bl(Value::Percent(40.0), None)?;
+/// ```
 /// # Errors
 /// Possible errors that can result from this function include:
 /// * All errors that can result from [``Device::new``]
 /// * [``BlibError::WriteNewVal``]
 /// * [``BlibError::ValueTooLarge``]
-pub fn set_bl(val: u32, device_name: Option<Cow<str>>) -> Result<(), BlibError> {
+pub fn set_bl(val: impl Into<Value>, device_name: Option<Cow<str>>) -> Result<(), BlibError> {
     let device = Device::new(device_name)?;
+    let val = device.to_raw(val.into());
 
     if val != device.current {
         device.write_value(val)?;
@@ -384,74 +550,80 @@ pub fn set_bl(val: u32, device_name: Option<Cow<str>>) -> Result<(), BlibError>
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::error::Error;
-    const TESTDIR: &str = "testbldir";
+    use crate::{err::Tip, fixture::Fixture};
+    use std::thread;
 
     #[test]
-    fn detecting_device_nvidia() {
-        clean_up();
-        setup_test_env(&["nvidia_0", "generic"]).unwrap();
-        let name = Device::detect_device(TESTDIR);
-        assert!(name.is_ok());
-        assert_eq!(name.unwrap(), "nvidia_0");
-        clean_up();
+    fn detecting_device_firmware() {
+        let (_fixture, root) = setup_test_env(
+            "detectfirmware",
+            &["intel_backlight", "dell_backlight", "acpi_video0"],
+        );
+        set_type(&root, "intel_backlight", "raw");
+        set_type(&root, "dell_backlight", "platform");
+        set_type(&root, "acpi_video0", "firmware");
+        let name = Device::detect_device(&root);
+        assert_eq!(name.unwrap(), "acpi_video0");
     }
 
     #[test]
-    fn detecting_device_amd() {
-        clean_up();
-        setup_test_env(&["nvidia_0", "generic", "amdgpu_x"]).unwrap();
-        let name = Device::detect_device(TESTDIR);
-        assert!(name.is_ok());
-        assert_eq!(name.unwrap(), "amdgpu_x");
-        clean_up();
+    fn detecting_device_platform() {
+        let (_fixture, root) = setup_test_env(
+            "detectplatform",
+            &["amdgpu_bl0", "nvidia_wmi_ec_backlight", "generic"],
+        );
+        set_type(&root, "amdgpu_bl0", "raw");
+        set_type(&root, "nvidia_wmi_ec_backlight", "platform");
+        let name = Device::detect_device(&root);
+        assert_eq!(name.unwrap(), "nvidia_wmi_ec_backlight");
     }
 
     #[test]
-    fn detecting_device_acpi() {
-        clean_up();
-        setup_test_env(&["acpi_video0", "generic"]).unwrap();
-        let name = Device::detect_device(TESTDIR);
-        assert!(name.is_ok());
-        assert_eq!(name.unwrap(), "acpi_video0");
-        clean_up();
+    fn detecting_device_raw() {
+        let (_fixture, root) = setup_test_env("detectraw", &["nvidia_0", "generic"]);
+        set_type(&root, "nvidia_0", "raw");
+        let name = Device::detect_device(&root);
+        assert_eq!(name.unwrap(), "nvidia_0");
     }
 
     #[test]
     fn detecting_device_fallback() {
-        clean_up();
-        setup_test_env(&["generic"]).unwrap();
-        let name = Device::detect_device(TESTDIR);
-        assert!(name.is_ok());
+        let (_fixture, root) = setup_test_env("detectfallback", &["generic"]);
+        let name = Device::detect_device(&root);
         assert_eq!(name.unwrap(), "generic");
-        clean_up();
     }
 
     #[test]
     fn writing_value() {
-        clean_up();
-        setup_test_env(&["generic"]).unwrap();
+        let (_fixture, root) = setup_test_env("writing", &["generic"]);
         let d = Device {
             name: "generic".to_string(),
             max: 100,
             current: 50,
-            device_dir: format!("{TESTDIR}/generic"),
+            path: root.join("generic"),
+            curve: BrightnessCurve::Linear,
+            min: 0,
+            #[cfg(feature = "logind")]
+            session: None,
         };
         d.write_value(100).unwrap();
-        let r = fs::read_to_string(format!("{TESTDIR}/generic/brightness"))
+        let r = fs::read_to_string(root.join("generic/brightness"))
             .expect("failed to read test backlight value");
         let res = r.trim();
         assert_eq!("100", res, "Result was {res}");
-        clean_up();
+
+        // the tip points at the file that couldn't be written, wherever the device lives
+        fs::remove_file(root.join("generic/brightness")).unwrap();
+        fs::create_dir(root.join("generic/brightness")).unwrap();
+        let tip = d.write_value(10).unwrap_err().tip().unwrap();
+        assert!(tip.contains(&root.join("generic/brightness").display().to_string()));
     }
 
     #[test]
     fn current_value() {
-        clean_up();
-        setup_test_env(&["generic"]).unwrap();
-        let current = Device::get_current(&format!("{TESTDIR}/generic")).unwrap();
+        let (_fixture, root) = setup_test_env("current", &["generic"]);
+        let current = Device::get_current(&root.join("generic")).unwrap();
         assert_eq!(current.to_string(), "50");
-        clean_up();
     }
 
     #[test]
@@ -460,7 +632,11 @@ mod tests {
             name: String::new(),
             current: 10,
             max: 100,
-            device_dir: String::new(),
+            path: PathBuf::new(),
+            curve: BrightnessCurve::Linear,
+            min: 0,
+            #[cfg(feature = "logind")]
+            session: None,
         };
         let ch = d.calculate_change(10, Direction::Inc);
         assert_eq!(ch, 20);
@@ -472,7 +648,11 @@ mod tests {
             name: String::new(),
             current: 30,
             max: 100,
-            device_dir: String::new(),
+            path: PathBuf::new(),
+            curve: BrightnessCurve::Linear,
+            min: 0,
+            #[cfg(feature = "logind")]
+            session: None,
         };
         let ch = d.calculate_change(10, Direction::Dec);
         assert_eq!(ch, 20);
@@ -484,7 +664,11 @@ mod tests {
             name: String::new(),
             current: 90,
             max: 100,
-            device_dir: String::new(),
+            path: PathBuf::new(),
+            curve: BrightnessCurve::Linear,
+            min: 0,
+            #[cfg(feature = "logind")]
+            session: None,
         };
         let ch = d.calculate_change(20, Direction::Inc);
         assert_eq!(ch, 100);
@@ -496,63 +680,230 @@ mod tests {
             name: String::new(),
             current: 10,
             max: 100,
-            device_dir: String::new(),
+            path: PathBuf::new(),
+            curve: BrightnessCurve::Linear,
+            min: 0,
+            #[cfg(feature = "logind")]
+            session: None,
         };
         let ch = d.calculate_change(20, Direction::Dec);
         assert_eq!(ch, 0);
     }
 
+    #[test]
+    fn logarithmic_steps() {
+        let mut d = Device {
+            name: String::new(),
+            current: 10,
+            max: 1000,
+            path: PathBuf::new(),
+            curve: BrightnessCurve::Logarithmic,
+            min: 0,
+            #[cfg(feature = "logind")]
+            session: None,
+        };
+        let low = d.calculate_change(5, Direction::Inc) - d.current;
+        d.current = 800;
+        let high = d.calculate_change(5, Direction::Inc) - d.current;
+        assert!(low < 50 && high > 50, "low step {low}, high step {high}");
+        d.current = 1000;
+        assert_eq!(d.calculate_change(100, Direction::Dec), 0);
+        assert_eq!(d.calculate_change(5, Direction::Inc), 1000);
+        assert_eq!(
+            d.calculate_change_with(10, Direction::Dec, BrightnessCurve::Linear),
+            900
+        );
+    }
+
+    #[test]
+    fn low_resolution_steps() {
+        for max in [1, 7, 15, 31] {
+            let mut d = Device {
+                name: String::new(),
+                current: 0,
+                max,
+                path: PathBuf::new(),
+                curve: BrightnessCurve::Linear,
+                min: 0,
+                #[cfg(feature = "logind")]
+                session: None,
+            };
+            for curve in [BrightnessCurve::Linear, BrightnessCurve::Logarithmic] {
+                d.curve = curve;
+                for current in 0..=max {
+                    d.current = current;
+                    let inc = d.calculate_change(1, Direction::Inc);
+                    let dec = d.calculate_change(1, Direction::Dec);
+                    assert_eq!(inc, (current + 1).min(max), "max {max}, {curve:?}");
+                    assert_eq!(dec, current.saturating_sub(1), "max {max}, {curve:?}");
+                    assert_eq!(d.calculate_change(0, Direction::Inc), current);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn low_resolution_sweep() {
+        let (_fixture, root) = setup_test_env("lowres", &["acpi_video0"]);
+        let mut d = test_device(&root, "acpi_video0");
+        d.max = 7;
+        d.write_value(2).unwrap();
+        d.reload();
+        d.sweep_write(7, Delay::from_millis(1)).unwrap();
+        d.reload();
+        assert_eq!(d.current, 7);
+        d.sweep_write(0, Delay::from_millis(1)).unwrap();
+        d.reload();
+        assert_eq!(d.current, 0);
+    }
+
+    #[test]
+    fn timed_sweep() {
+        let (_fixture, root) = setup_test_env("timedsweep", &["intel_backlight"]);
+        let mut d = test_device(&root, "intel_backlight");
+        d.max = 1000;
+        let duration = Duration::from_millis(100);
+        let start = std::time::Instant::now();
+        d.sweep_write_for(900, duration, Easing::EaseOut).unwrap();
+        assert!(start.elapsed() >= duration.mul_f64(0.9));
+        d.reload();
+        assert_eq!(d.current, 900);
+        d.sweep_write_for(0, Duration::ZERO, Easing::Perceptual)
+            .unwrap();
+        d.reload();
+        assert_eq!(d.current, 0);
+        assert!(d.sweep_write_for(1001, duration, Easing::Linear).is_err());
+    }
+
+    #[test]
+    fn background_sweep() {
+        let (_fixture, root) = setup_test_env("bgsweep", &["intel_backlight"]);
+        let mut d = test_device(&root, "intel_backlight");
+        d.max = 1000;
+
+        let sweep = d.sweep_in_background(200, Delay::from_millis(2)).unwrap();
+        assert!(sweep.retarget(600));
+        assert_eq!(sweep.target(), 600);
+        assert_eq!(sweep.join().unwrap(), 600);
+        d.reload();
+        assert_eq!(d.current, 600);
+
+        let sweep = d.sweep_in_background(0, Delay::from_millis(20)).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        sweep.cancel();
+        assert!(!sweep.retarget(1000));
+        let stopped = sweep.join().unwrap();
+        assert!(stopped > 0 && stopped < 600, "stopped at {stopped}");
+        d.reload();
+        assert_eq!(d.current, stopped);
+
+        assert!(d.sweep_in_background(1001, Delay::default()).is_err());
+    }
+
+    #[test]
+    fn percent_round_trip() {
+        for max in [7, 255, 120_000] {
+            let mut d = Device {
+                name: String::new(),
+                current: 0,
+                max,
+                path: PathBuf::new(),
+                curve: BrightnessCurve::Linear,
+                min: 0,
+                #[cfg(feature = "logind")]
+                session: None,
+            };
+            for raw in 0..=max {
+                d.current = raw;
+                assert_eq!(d.to_raw(Value::Percent(d.current_percent())), raw);
+            }
+        }
+    }
+
+    #[test]
+    fn percent_conversion() {
+        let d = Device {
+            name: String::new(),
+            current: 3,
+            max: 7,
+            path: PathBuf::new(),
+            curve: BrightnessCurve::Linear,
+            min: 0,
+            #[cfg(feature = "logind")]
+            session: None,
+        };
+        assert_eq!(d.to_raw(Value::Percent(50.0)), 4);
+        assert_eq!(d.to_raw(Value::Percent(100.0)), 7);
+        assert_eq!(d.to_raw(Value::Percent(150.0)), 7);
+        assert_eq!(d.to_raw(Value::Percent(-5.0)), 0);
+        assert_eq!(d.to_raw(Value::Raw(5)), 5);
+        assert!((d.to_percent(7) - 100.0).abs() < f64::EPSILON);
+    }
+
     #[test]
     fn sweeping() {
-        clean_up();
-        setup_test_env(&["generic"]).unwrap();
-        let mut d = test_device("generic");
+        let (_fixture, root) = setup_test_env("sweeping", &["generic"]);
+        let mut d = test_device(&root, "generic");
         d.sweep_write(100, Delay::default()).unwrap();
         d.reload();
         assert_eq!(d.current, 100);
         d.sweep_write(0, Delay::default()).unwrap();
         d.reload();
         assert_eq!(d.current, 0);
-        clean_up();
     }
 
     #[test]
     fn sweep_bounds() {
-        clean_up();
-        setup_test_env(&["generic"]).unwrap();
-        let mut d = test_device("generic");
+        let (_fixture, root) = setup_test_env("sweepbounds", &["generic"]);
+        let mut d = test_device(&root, "generic");
         d.write_value(0).unwrap();
         d.sweep_write(u32::MAX, Delay::default()).unwrap();
         d.reload();
         assert_eq!(d.current, 0);
-        clean_up();
     }
 
-    fn setup_test_env(dirs: &[&str]) -> Result<(), Box<dyn Error>> {
-        fs::create_dir(TESTDIR)?;
+    #[test]
+    fn loading_from_custom_root() {
+        let root = Fixture::new("sysroot");
+        let dev = root.device(BLCLASS, "intel_backlight", 120, 255);
+
+        let detected = Device::new_in(&root, None).unwrap();
+        assert_eq!(detected.name(), "intel_backlight");
+        assert_eq!(detected.current(), 120);
+        assert_eq!(detected.max(), 255);
+        assert_eq!(detected.device_path(), dev);
+
+        assert!(Device::new_in(&root, Some("intel_backlight".into())).is_ok());
+        assert!(matches!(
+            Device::new_in(&root, Some("nvidia_0".into())),
+            Err(BlibError::NoDeviceFound)
+        ));
+    }
+
+    /// Returns the fixture along with its backlight class directory, which holds the given devices at 50/100
+    fn setup_test_env(name: &str, dirs: &[&str]) -> (Fixture, PathBuf) {
+        let fixture = Fixture::new(name);
         for dir in dirs {
-            fs::create_dir(format!("{TESTDIR}/{dir}"))?;
-            fs::write(format!("{TESTDIR}/{dir}/brightness"), "50")?;
-            fs::write(format!("{TESTDIR}/{dir}/max"), "100")?;
+            fixture.device(BLCLASS, dir, 50, 100);
         }
-        Ok(())
+        let root = fixture.join(BLCLASS);
+        (fixture, root)
     }
 
-    fn test_device(name: &str) -> Device {
+    fn set_type(root: &Path, dir: &str, kind: &str) {
+        fs::write(root.join(dir).join("type"), kind).unwrap();
+    }
+
+    fn test_device(root: &Path, name: &str) -> Device {
         Device {
             name: name.into(),
             current: 50,
             max: 100,
-            device_dir: format!("{TESTDIR}/{name}"),
-        }
-    }
-
-    fn clean_up() {
-        if fs::read_dir(".")
-            .unwrap()
-            .any(|dir| dir.unwrap().file_name().as_os_str() == "testbldir")
-        {
-            fs::remove_dir_all(TESTDIR).expect("Failed to clean up testing backlight directory.");
+            path: root.join(name),
+            curve: BrightnessCurve::Linear,
+            min: 0,
+            #[cfg(feature = "logind")]
+            session: None,
         }
     }
 }
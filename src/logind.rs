@@ -0,0 +1,224 @@
+//! Brightness changes through systemd-logind, which doesn't require write access to sysfs. See [``Session``]
+//!
+//! logind lets the active session set the brightness of backlight and LED devices with `org.freedesktop.login1.Session.SetBrightness`.
+//! [``Device::write_value``] and the sweep methods fall back to it on their own when the brightness file isn't writable by the current user,
+//! so the udev rules and video group membership set up by `blight setup` are only needed on systems without logind.
+
+use crate::{backlight::check_max, BlResult, Device};
+use std::{
+    io,
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
+use zbus::blocking::Connection;
+
+const DESTINATION: &str = "org.freedesktop.login1";
+const INTERFACE: &str = "org.freedesktop.login1.Session";
+/// logind resolves this path to the session of the caller
+const SESSION_PATH: &str = "/org/freedesktop/login1/session/auto";
+
+/// A connection to the logind session of the current process
+#[derive(Clone, Debug)]
+pub struct Session {
+    conn: Connection,
+}
+
+impl Session {
+    /// Connects to logind on the system bus
+    /// # Errors
+    /// If the system bus can't be reached
+    pub fn connect() -> io::Result<Self> {
+        Connection::system()
+            .map(Self::with_connection)
+            .map_err(io::Error::other)
+    }
+
+    /// Uses an existing D-Bus connection, for example one to a stand-in of logind in tests
+    #[must_use]
+    pub fn with_connection(conn: Connection) -> Self {
+        Self { conn }
+    }
+
+    /// Returns the session on the system bus shared by all devices, connecting on first use.
+    /// Returns None if the system bus can't be reached. Failures aren't remembered, so a long running process starting before D-Bus connects once it's up.
+    pub(crate) fn system() -> Option<Self> {
+        static SYSTEM: Mutex<Option<Session>> = Mutex::new(None);
+        let mut system = SYSTEM.lock().unwrap_or_else(PoisonError::into_inner);
+        if system.is_none() {
+            *system = Self::connect().ok();
+        }
+        system.clone()
+    }
+
+    /// Asks logind to set the brightness of the device with the given name to a raw value.
+    /// The subsystem is either `backlight` or `leds`.
+    /// # Errors
+    /// If the call fails, for example because the session isn't active or the device doesn't exist
+    pub fn set_brightness(&self, subsystem: &str, name: &str, value: u32) -> io::Result<()> {
+        self.conn
+            .call_method(
+                Some(DESTINATION),
+                SESSION_PATH,
+                Some(INTERFACE),
+                "SetBrightness",
+                &(subsystem, name, value),
+            )
+            .map(drop)
+            .map_err(io::Error::other)
+    }
+}
+
+impl Device {
+    /// Sets the session that writes fall back to when the brightness file isn't writable, instead of the one on the system bus
+    pub fn set_session(&mut self, session: Session) {
+        self.session = Some(session);
+    }
+
+    /// Writes a value through the given logind session instead of the brightness file, see [``Session``]
+    /// # Errors
+    /// * [``BlibError::ValueTooLarge``][crate::err::BlibError::ValueTooLarge]
+    /// * [``BlibError::WriteNewVal``][crate::err::BlibError::WriteNewVal] - if logind rejects the change
+    pub fn write_through(&self, session: &Session, value: u32) -> BlResult<()> {
+        check_max(value, self.max)?;
+        session
+            .set_brightness(self.subsystem(), &self.name, value)
+            .map_err(|err| self.write_error(err))
+    }
+
+    /// Returns the logind subsystem of the device, `leds` for devices under `/sys/class/leds` and `backlight` otherwise
+    pub(crate) fn subsystem(&self) -> &'static str {
+        match self.path.parent().and_then(Path::file_name) {
+            Some(class) if class == "leds" => "leds",
+            _ => "backlight",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{err::BlibError, fixture::Fixture, BrightnessCurve, Delay, Device, BLCLASS};
+    use rustix::{
+        process::{geteuid, Gid, Uid},
+        thread::{set_thread_gid, set_thread_uid},
+    };
+    use std::{
+        fs,
+        os::unix::{fs::PermissionsExt, net::UnixStream},
+        path::PathBuf,
+        sync::{Arc, Mutex},
+        thread,
+    };
+    use zbus::{
+        blocking::{connection::Builder, Connection},
+        fdo, Guid,
+    };
+
+    type Calls = Arc<Mutex<Vec<(String, String, u32)>>>;
+
+    /// Stands in for logind, recording the calls it receives
+    struct StandIn(Calls);
+
+    #[zbus::interface(name = "org.freedesktop.login1.Session")]
+    impl StandIn {
+        fn set_brightness(&self, subsystem: &str, name: &str, value: u32) -> fdo::Result<()> {
+            if name == "missing" {
+                return Err(fdo::Error::FileNotFound(name.to_owned()));
+            }
+            self.0
+                .lock()
+                .unwrap()
+                .push((subsystem.to_owned(), name.to_owned(), value));
+            Ok(())
+        }
+    }
+
+    fn device(path: &str, max: u32) -> Device {
+        let path = PathBuf::from(path);
+        Device {
+            name: path.file_name().unwrap().to_string_lossy().into_owned(),
+            current: 0,
+            max,
+            path,
+            curve: BrightnessCurve::Linear,
+            min: 0,
+            session: None,
+        }
+    }
+
+    /// Connects a session to a stand-in, the server connection has to be kept alive for as long as the session is used
+    fn stand_in() -> (Session, Calls, Connection) {
+        let calls = Calls::default();
+        let (server, client) = UnixStream::pair().unwrap();
+        let stand_in = StandIn(Arc::clone(&calls));
+        let server = thread::spawn(move || {
+            Builder::async_io_unix_stream(server)
+                .server(Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at(SESSION_PATH, stand_in)
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let client = Builder::async_io_unix_stream(client).p2p().build().unwrap();
+        let server = server.join().unwrap();
+        (Session::with_connection(client), calls, server)
+    }
+
+    #[test]
+    fn setting_brightness_through_logind() {
+        let (session, calls, _server) = stand_in();
+
+        let backlight = device("/sys/class/backlight/intel_backlight", 100);
+        backlight.write_through(&session, 40).unwrap();
+        device("/sys/class/leds/tpacpi::kbd_backlight", 2)
+            .write_through(&session, 2)
+            .unwrap();
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                ("backlight".to_owned(), "intel_backlight".to_owned(), 40),
+                ("leds".to_owned(), "tpacpi::kbd_backlight".to_owned(), 2)
+            ]
+        );
+
+        assert!(matches!(
+            backlight.write_through(&session, 101),
+            Err(BlibError::ValueTooLarge { .. })
+        ));
+        assert!(matches!(
+            device("/sys/class/backlight/missing", 10).write_through(&session, 1),
+            Err(BlibError::WriteNewVal { .. })
+        ));
+    }
+
+    #[test]
+    fn falling_back_to_logind() {
+        let (session, calls, _server) = stand_in();
+        let root = Fixture::new("logind");
+        let dir = root.device(BLCLASS, "intel_backlight", 10, 100);
+        fs::set_permissions(&root, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(dir.join("brightness"), fs::Permissions::from_mode(0o444)).unwrap();
+
+        let mut device = Device::new_in(&root, Some("intel_backlight".into())).unwrap();
+        device.set_session(session);
+        // root can write to read-only files, so the writes run on a thread that gave up root
+        thread::spawn(move || {
+            if geteuid().is_root() {
+                // SAFETY: 65534 (nobody) isn't -1, the only invalid id
+                let (gid, uid) = unsafe { (Gid::from_raw(65534), Uid::from_raw(65534)) };
+                set_thread_gid(gid).unwrap();
+                set_thread_uid(uid).unwrap();
+            }
+            device.write_value(40).unwrap();
+            device.sweep_write(12, Delay::from_millis(0)).unwrap();
+        })
+        .join()
+        .unwrap();
+
+        let values: Vec<u32> = calls.lock().unwrap().iter().map(|call| call.2).collect();
+        assert_eq!(values, [40, 11, 12]);
+        assert_eq!(fs::read_to_string(dir.join("brightness")).unwrap(), "10\n");
+    }
+}
@@ -1,9 +1,13 @@
 use std::env;
 
+#[cfg(test)]
+#[path = "fixture.rs"]
+mod fixture;
 mod utils;
 
 fn main() {
     utils::PanicReporter::init();
+    utils::init_color();
 
     let config = match utils::parse(env::args().skip(1)) {
         Ok(c) => c,
@@ -0,0 +1,277 @@
+//! An in-memory backlight for testing code that uses blight without root or real hardware. See [``MockDevice``]
+//!
+//! Only available with the `testing` feature, which is meant to be enabled in `[dev-dependencies]`.
+
+use crate::{backlight::check_max, err::BlibError, Backlight, BlResult, BrightnessCurve};
+use std::{
+    io,
+    ops::RangeInclusive,
+    path::Path,
+    sync::{Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A backlight that lives in memory and records every write made to it.
+///
+/// It implements [``Backlight``] and mirrors the behavior of [``Device``][crate::Device]: values larger than max are rejected,
+/// and [``Backlight::current``] is a snapshot that is only updated by [``MockDevice::reload``], while [``MockDevice::value``] returns what the "hardware" holds.
+/// The mock can also be told to misbehave, to test how code copes with real world devices:
+/// * [``MockDevice::set_clamp``] - the hardware silently clamps written values to a smaller range
+/// * [``MockDevice::set_lag``] - every write blocks for a while before it's applied
+/// * [``MockDevice::set_read_only``] - writes fail as if the brightness file wasn't writable
+/// * [``MockDevice::unplug``] - the device disappears, so writes fail as if its directory was gone
+///
+/// All of these take `&self`, so they can be changed while the mock is shared with a sweep running in another thread.
+/// # Examples
+/// ```ignore
+/// use blight::{mock::MockDevice, Backlight, Change, Direction};
+///
+/// let mut mock = MockDevice::new("intel_backlight", 50, 100);
+/// mock.change(10, Change::Regular, Direction::Inc)?;
+/// assert_eq!(mock.values(), [60]);
+/// mock.reload();
+/// assert_eq!(mock.current(), 60);
+/// ```
+#[derive(Debug)]
+pub struct MockDevice {
+    name: String,
+    current: u32,
+    max: u32,
+    curve: BrightnessCurve,
+    min: u32,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    value: u32,
+    writes: Vec<Write>,
+    clamp: Option<RangeInclusive<u32>>,
+    lag: Duration,
+    read_only: bool,
+    unplugged: bool,
+}
+
+/// A write made to a [``MockDevice``], as returned by [``MockDevice::writes``]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Write {
+    /// The value that was written
+    pub requested: u32,
+    /// The value the device ended up with, which differs from the requested one if it was clamped
+    pub applied: u32,
+    /// When the value was applied
+    pub at: Instant,
+}
+
+impl MockDevice {
+    /// Creates a mock with the given name, current and max brightness values and a linear curve
+    #[must_use]
+    pub fn new(name: impl Into<String>, current: u32, max: u32) -> Self {
+        Self {
+            name: name.into(),
+            current,
+            max,
+            curve: BrightnessCurve::Linear,
+            min: 0,
+            state: Mutex::new(State {
+                value: current,
+                writes: Vec::new(),
+                clamp: None,
+                lag: Duration::ZERO,
+                read_only: false,
+                unplugged: false,
+            }),
+        }
+    }
+
+    /// Sets the curve used by [``Backlight::calculate_change``], like [``Device::set_curve``][crate::Device::set_curve]
+    pub fn set_curve(&mut self, curve: BrightnessCurve) {
+        self.curve = curve;
+    }
+
+    /// Sets the floor that changes don't go below, like [``Device::set_min``][crate::Device::set_min]
+    pub fn set_min(&mut self, min: u32) {
+        self.min = min;
+    }
+
+    /// Writes a value to the mock, same as [``Backlight::write_raw``]. Exists so that code written against [``Device::write_value``][crate::Device::write_value] works unchanged.
+    /// # Errors
+    /// * [``BlibError::ValueTooLarge``]
+    /// * [``BlibError::WriteNewVal``] - if the mock is read-only or unplugged
+    pub fn write_value(&self, value: u32) -> BlResult<()> {
+        check_max(value, self.max)?;
+        let lag = self.state().lag;
+        thread::sleep(lag);
+        let mut state = self.state();
+        let fail = |kind| {
+            Err(BlibError::WriteNewVal {
+                err: io::Error::from(kind),
+                dev: self.name.clone(),
+                // where the brightness file of a real backlight with this name would be
+                path: Path::new(crate::BLDIR).join(&self.name).join("brightness"),
+            })
+        };
+        if state.unplugged {
+            return fail(io::ErrorKind::NotFound);
+        }
+        if state.read_only {
+            return fail(io::ErrorKind::PermissionDenied);
+        }
+        let applied = match &state.clamp {
+            Some(range) => value.clamp(*range.start(), *range.end()),
+            None => value,
+        };
+        state.value = applied;
+        state.writes.push(Write {
+            requested: value,
+            applied,
+            at: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// Updates the current value to what the mock holds, like [``Device::reload``][crate::Device::reload]
+    /// # Panics
+    /// If the mock has been unplugged, just like reloading a device that disappeared
+    pub fn reload(&mut self) {
+        let (value, unplugged) = {
+            let state = self.state();
+            (state.value, state.unplugged)
+        };
+        assert!(!unplugged, "failed to reload unplugged mock device");
+        self.current = value;
+    }
+
+    /// Returns the value the mock currently holds, which [``Backlight::current``] only catches up with on [``MockDevice::reload``]
+    #[must_use]
+    pub fn value(&self) -> u32 {
+        self.state().value
+    }
+
+    /// Returns every successful write so far, oldest first
+    #[must_use]
+    pub fn writes(&self) -> Vec<Write> {
+        self.state().writes.clone()
+    }
+
+    /// Returns the requested values of every successful write so far, oldest first
+    #[must_use]
+    pub fn values(&self) -> Vec<u32> {
+        self.state().writes.iter().map(|w| w.requested).collect()
+    }
+
+    /// Forgets all recorded writes
+    pub fn clear_writes(&self) {
+        self.state().writes.clear();
+    }
+
+    /// Makes the mock clamp written values to the given range, like panels that won't go below a certain level. None turns clamping off.
+    ///
+    /// Values larger than max are still rejected.
+    pub fn set_clamp(&self, range: Option<RangeInclusive<u32>>) {
+        self.state().clamp = range;
+    }
+
+    /// Makes every write block for the given duration before it's applied, like slow firmware backlights
+    pub fn set_lag(&self, lag: Duration) {
+        self.state().lag = lag;
+    }
+
+    /// Makes writes fail with a permission error, or succeed again
+    pub fn set_read_only(&self, read_only: bool) {
+        self.state().read_only = read_only;
+    }
+
+    /// Makes the mock disappear, like a device whose driver was unloaded. Writes fail from then on and [``MockDevice::reload``] panics.
+    pub fn unplug(&self) {
+        self.state().unplugged = true;
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl Backlight for MockDevice {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn current(&self) -> u32 {
+        self.current
+    }
+
+    fn max(&self) -> u32 {
+        self.max
+    }
+
+    /// Same as [``MockDevice::write_value``]
+    fn write_raw(&self, value: u32) -> BlResult<()> {
+        self.write_value(value)
+    }
+
+    fn curve(&self) -> BrightnessCurve {
+        self.curve
+    }
+
+    fn min(&self) -> u32 {
+        self.min
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Change, Delay, Direction};
+
+    #[test]
+    fn recording_writes() {
+        let mut mock = MockDevice::new("mock", 50, 100);
+        mock.change(10, Change::Regular, Direction::Inc).unwrap();
+        assert_eq!((mock.current(), mock.value()), (50, 60));
+        mock.reload();
+        assert_eq!(mock.current(), 60);
+
+        mock.sweep_write(63, Delay::from_millis(1)).unwrap();
+        assert_eq!(mock.values(), [60, 61, 62, 63]);
+        let writes = mock.writes();
+        assert!(writes.windows(2).all(|w| w[0].at <= w[1].at));
+        assert!(matches!(
+            mock.write_value(101),
+            Err(BlibError::ValueTooLarge { .. })
+        ));
+        mock.clear_writes();
+        assert!(mock.writes().is_empty());
+    }
+
+    #[test]
+    fn misbehaving() {
+        let mock = MockDevice::new("mock", 50, 100);
+        mock.set_clamp(Some(5..=100));
+        mock.write_value(0).unwrap();
+        assert_eq!(mock.writes()[0].applied, 5);
+        assert_eq!(mock.value(), 5);
+
+        mock.set_lag(Duration::from_millis(20));
+        let start = Instant::now();
+        mock.write_value(30).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        mock.set_lag(Duration::ZERO);
+
+        mock.set_read_only(true);
+        let err = mock.write_value(40).unwrap_err();
+        assert!(
+            matches!(err, BlibError::WriteNewVal { ref err, .. } if err.kind() == io::ErrorKind::PermissionDenied)
+        );
+        mock.set_read_only(false);
+        mock.unplug();
+        let err = mock.write_value(40).unwrap_err();
+        assert!(
+            matches!(err, BlibError::WriteNewVal { ref err, .. } if err.kind() == io::ErrorKind::NotFound)
+        );
+        assert_eq!(mock.value(), 30);
+    }
+}
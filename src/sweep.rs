@@ -0,0 +1,281 @@
+//! Duration based and background sweeps. See [`Easing`], [``Backlight::sweep_write_for``]
+//! and [``Device::sweep_in_background``][crate::Device::sweep_in_background]
+
+use crate::{
+    backlight::{check_max, sweep_rate},
+    err::BlibError,
+    Backlight, BlResult, BrightnessCurve, Delay, Device,
+};
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Easing function used by [``Backlight::sweep_write_for``] to compute the intermediate values of a sweep.
+///
+/// An easing function maps the elapsed fraction of the sweep duration (0.0-1.0) to the fraction of the distance travelled.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Easing {
+    /// Constant speed from start to end
+    #[default]
+    Linear,
+    /// Starts slow, speeds up in the middle and slows down towards the end
+    EaseInOut,
+    /// Starts fast and slows down towards the end
+    EaseOut,
+    /// Constant speed in perceived brightness. This follows the device's curve when it is non-linear,
+    /// and the [logarithmic][BrightnessCurve::Logarithmic] curve otherwise.
+    Perceptual,
+}
+
+impl Easing {
+    /// Returns the fraction of the distance travelled at the given fraction of the duration
+    #[must_use]
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Linear | Self::Perceptual => t,
+            Self::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t.powi(3)
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Self::EaseOut => 1.0 - (1.0 - t).powi(3),
+        }
+    }
+
+    fn curve(self, device_curve: BrightnessCurve) -> BrightnessCurve {
+        match (self, device_curve) {
+            (Self::Perceptual, BrightnessCurve::Linear) => BrightnessCurve::Logarithmic,
+            (Self::Perceptual, curve) => curve,
+            _ => BrightnessCurve::Linear,
+        }
+    }
+}
+
+/// Drives a duration based sweep, handing every intermediate value to the writer
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+pub(crate) fn timed_sweep<B: Backlight + ?Sized>(
+    device: &B,
+    value: u32,
+    duration: Duration,
+    easing: Easing,
+    mut write: impl FnMut(u32) -> BlResult<()>,
+) -> BlResult<()> {
+    let (current, max) = (device.current(), device.max());
+    check_max(value, max)?;
+    let value = device.floor(value);
+    if value == current {
+        return Ok(());
+    }
+    let interval = *Delay::default();
+    let frames = (duration.as_nanos() / interval.as_nanos()).clamp(1, u128::from(u32::MAX)) as u32;
+    let curve = easing.curve(device.curve());
+    let maxf = f64::from(max);
+    let from = curve.to_perceived(f64::from(current) / maxf);
+    let to = curve.to_perceived(f64::from(value) / maxf);
+
+    let start = Instant::now();
+    let mut last = current;
+    for frame in 1..=frames {
+        let deadline = start + duration.mul_f64(f64::from(frame) / f64::from(frames));
+        thread::sleep(deadline.saturating_duration_since(Instant::now()));
+        let progress = easing.apply(f64::from(frame) / f64::from(frames));
+        let next = if frame == frames {
+            value
+        } else {
+            (curve.to_raw(from + (to - from) * progress) * maxf).round() as u32
+        };
+        if next != last {
+            write(next)?;
+            last = next;
+        }
+    }
+    Ok(())
+}
+
+impl Device {
+    /// Starts a sweep towards the target value on a separate thread and returns a [``SweepHandle``] to control it.
+    ///
+    /// The sweep moves in steps of 1% (or one raw unit on devices with less than 100 levels) with the given delay in between, like [``Backlight::sweep_write``].
+    /// Unlike [``Backlight::sweep_write``] though, the target can be changed while the sweep is running using [``SweepHandle::retarget``],
+    /// in which case the sweep simply continues towards the new target, and it can be stopped early using [``SweepHandle::cancel``].
+    /// Targets below the device's [floor][Backlight::min] are raised to it, including the ones given to [``SweepHandle::retarget``].
+    /// # Example
+    /// ```ignore
+    /// let dev = Device::new(None)?;
+    /// let sweep = dev.sweep_in_background(dev.calculate_change(10, Direction::Inc), Delay::default())?;
+    /// // user pressed brightness up again while the sweep was running
+    /// sweep.retarget(sweep.target() + 10);
+    /// let reached = sweep.join()?;
+    /// ```
+    /// # Errors
+    /// * [``BlibError::ValueTooLarge``]
+    pub fn sweep_in_background(&self, value: u32, delay: Delay) -> BlResult<SweepHandle> {
+        check_max(value, self.max)?;
+        let state = Arc::new(Mutex::new(SweepState {
+            target: self.floor(value),
+            current: self.current,
+            cancelled: false,
+            finished: false,
+        }));
+        let device = self.clone();
+        let shared = Arc::clone(&state);
+        let thread = thread::spawn(move || device.background_sweep(&shared, delay));
+        Ok(SweepHandle {
+            state,
+            min: self.floor(0),
+            max: self.max,
+            thread,
+        })
+    }
+
+    fn background_sweep(&self, state: &Mutex<SweepState>, delay: Delay) -> BlResult<u32> {
+        let finish = |current| {
+            let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+            state.finished = true;
+            state.current = current;
+        };
+        let mut writer = match self.open_writer() {
+            Ok(writer) => writer,
+            Err(err) => {
+                finish(self.current);
+                return Err(BlibError::SweepError(err));
+            }
+        };
+        let rate = sweep_rate(self.max);
+        let mut current = self.current;
+        loop {
+            let target = {
+                let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+                if state.cancelled || state.target == current {
+                    state.finished = true;
+                    return Ok(current);
+                }
+                state.target
+            };
+            current = step_towards(current, target, rate);
+            if let Err(err) = writer.write(current) {
+                finish(current);
+                return Err(BlibError::SweepError(err));
+            }
+            state.lock().unwrap_or_else(PoisonError::into_inner).current = current;
+            thread::sleep(*delay);
+        }
+    }
+}
+
+/// Moves the value towards the target by the given rate, without overshooting it
+pub(crate) fn step_towards(current: u32, target: u32, rate: u32) -> u32 {
+    if target > current {
+        current.saturating_add(rate).min(target)
+    } else {
+        current.saturating_sub(rate).max(target)
+    }
+}
+
+#[derive(Debug)]
+struct SweepState {
+    target: u32,
+    current: u32,
+    cancelled: bool,
+    finished: bool,
+}
+
+/// A handle to a sweep running in the background, returned by [``Device::sweep_in_background``].
+///
+/// Dropping the handle does not stop the sweep, use [``SweepHandle::cancel``] for that.
+#[derive(Debug)]
+pub struct SweepHandle {
+    state: Arc<Mutex<SweepState>>,
+    min: u32,
+    max: u32,
+    thread: JoinHandle<BlResult<u32>>,
+}
+
+impl SweepHandle {
+    fn state(&self) -> std::sync::MutexGuard<'_, SweepState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Stops the sweep after the current step, leaving the brightness at whatever value was written last
+    pub fn cancel(&self) {
+        self.state().cancelled = true;
+    }
+
+    /// Changes the destination of the running sweep, which continues towards the new value from wherever it currently is.
+    ///
+    /// Values larger than the max brightness of the device are clamped to max, values below its floor are raised to the floor.
+    /// Returns false if the sweep has already finished (or was cancelled), in which case nothing changes and a new sweep has to be started instead.
+    #[must_use]
+    pub fn retarget(&self, value: u32) -> bool {
+        let mut state = self.state();
+        if state.finished || state.cancelled {
+            return false;
+        }
+        state.target = value.clamp(self.min, self.max);
+        true
+    }
+
+    /// Returns the value the sweep is currently heading towards
+    #[must_use]
+    pub fn target(&self) -> u32 {
+        self.state().target
+    }
+
+    /// Returns the last value written by the sweep
+    #[must_use]
+    pub fn current(&self) -> u32 {
+        self.state().current
+    }
+
+    /// Returns true if the sweep has reached its target, was cancelled, or failed
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.state().finished
+    }
+
+    /// Waits for the sweep to finish and returns the last value that was written.
+    /// # Errors
+    /// * [``BlibError::SweepError``]
+    /// # Panics
+    /// Resumes the panic if the sweep thread panicked.
+    pub fn join(self) -> BlResult<u32> {
+        self.thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn easing_bounds() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseInOut,
+            Easing::EaseOut,
+            Easing::Perceptual,
+        ] {
+            assert!(easing.apply(0.0).abs() < 1e-9, "{easing:?}");
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-9, "{easing:?}");
+            let mut last = 0.0;
+            for i in 1..=100 {
+                let v = easing.apply(f64::from(i) / 100.0);
+                assert!(v >= last, "{easing:?} is not monotonic");
+                last = v;
+            }
+        }
+        assert!(Easing::EaseOut.apply(0.5) > 0.5);
+        assert!(Easing::EaseInOut.apply(0.25) < 0.25);
+    }
+}
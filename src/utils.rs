@@ -1,25 +1,37 @@
 use blight::{
+    curve::DEFAULT_GAMMA,
     err::{BlibError, Tip},
-    Change, Device,
+    Backlight, BacklightType, BlPower, BrightnessCurve, Change, Connector, Delay, Device,
+    DeviceGroup, DeviceInfo,
     Direction::{self, Dec, Inc},
-    BLDIR,
+    Easing, Gpu, GpuKind, Monitor, Value,
 };
 use colored::Colorize;
+use config::{Defaults, Limits};
 use fs4::FileExt;
 use std::{
     borrow::Cow,
+    collections::BTreeMap,
     env,
     env::Args,
     error::Error,
     fs::{self, File, OpenOptions},
+    io::{self, IsTerminal},
     iter::Skip,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
+mod config;
+mod json;
 mod setup;
 
 const SAVEDIR: &str = "/.local/share/blight";
 const LOCKFILE: &str = "/tmp/blight.lock";
+/// Environment variable that overrides the sysfs root, which lets the CLI run against a fixture directory
+const ROOT_VAR: &str = "BLIGHT_SYSFS_ROOT";
+/// Sweep duration used when only an easing function is given
+const DEFAULT_DURATION: Duration = Duration::from_millis(300);
 
 type DynError = Box<dyn Error + 'static>;
 
@@ -33,74 +45,194 @@ enum Command {
     Help,
     ShortHelp,
     Status,
+    Get,
     Save,
     Restore,
     List,
-    Adjust { dir: Direction, value: u32 },
-    Set(u32),
+    KbdList,
+    Monitor,
+    Adjust { dir: Direction, value: Value },
+    Set(Value),
 }
 
 #[derive(Default)]
 struct Options<'a> {
     device: Option<Cow<'a, str>>,
     sweep: Change,
+    curve: Option<BrightnessCurve>,
+    duration: Option<Duration>,
+    easing: Option<Easing>,
+    all: bool,
+    /// Set by the `kbd` command, targets keyboard backlights instead of backlight devices
+    keyboard: bool,
+    /// Step of inc and dec when no value is given
+    step: Option<u32>,
+    limits: BTreeMap<String, Limits>,
+    /// Set by --allow-off, lets changes go below the floor of the device
+    allow_off: bool,
+    /// Set by --raw, makes the values of inc and dec raw units instead of percentages, and get print a raw value
+    raw: bool,
+    /// Set by --json, prints status, list and get as JSON, see [``json``]
+    json: bool,
 }
 
 impl Options<'_> {
-    fn set(mut self, arg: String) -> Self {
-        match arg.as_str() {
-            "-d" | "--device" => self.device = Some("".into()),
-            "-s" | "--sweep" => self.sweep = Change::Sweep,
-            _ => {
-                if let Some(d) = &mut self.device {
-                    if d.is_empty() {
-                        *d = Cow::from(arg);
-                    }
+    /// Starts out with the defaults from config files and environment variables, which flags then override
+    fn parse(
+        mut args: impl Iterator<Item = String>,
+        defaults: &Defaults,
+    ) -> Result<Self, BlightError> {
+        let mut options = Self::from_defaults(defaults)?;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                // Device::new_in resolves output names to the device that lights up the panel
+                "-d" | "--device" | "-o" | "--output" => {
+                    options.device = Some(args.next().ok_or(BlightError::MissingValue)?.into());
+                }
+                "-s" | "--sweep" => options.sweep = Change::Sweep,
+                "--no-sweep" => options.sweep = Change::Regular,
+                "-a" | "--all" => options.all = true,
+                "--allow-off" => options.allow_off = true,
+                "--raw" => options.raw = true,
+                "--json" => options.json = true,
+                "-c" | "--curve" => {
+                    let curve = args.next().ok_or(BlightError::MissingValue)?;
+                    options.curve = Some(parse_curve(&curve)?);
                 }
+                "--duration" => {
+                    let duration = args.next().ok_or(BlightError::MissingValue)?;
+                    options.duration = Some(parse_duration(&duration)?);
+                }
+                "--easing" => {
+                    let easing = args.next().ok_or(BlightError::MissingValue)?;
+                    options.easing = Some(parse_easing(&easing)?);
+                }
+                _ => (),
             }
         }
-        self
+        Ok(options)
+    }
+
+    fn from_defaults(defaults: &Defaults) -> Result<Self, BlightError> {
+        Ok(Self {
+            device: defaults.device.clone().map(Cow::from),
+            sweep: match defaults.sweep {
+                Some(true) => Change::Sweep,
+                _ => Change::Regular,
+            },
+            curve: defaults.curve.as_deref().map(parse_curve).transpose()?,
+            duration: defaults
+                .duration
+                .as_deref()
+                .map(parse_duration)
+                .transpose()?,
+            easing: defaults.easing.as_deref().map(parse_easing).transpose()?,
+            step: defaults.step,
+            limits: defaults.devices.clone(),
+            ..Self::default()
+        })
+    }
+
+    /// Keeps the value below the max configured for the device, the configured min is handled by [``Options::floor``]
+    fn limit(&self, device: &str, value: u32) -> u32 {
+        let max = self.limits.get(device).and_then(|limits| limits.max);
+        value.min(max.unwrap_or(u32::MAX))
+    }
+
+    /// Returns the floor of the device: 0 with --allow-off, otherwise the configured min,
+    /// or a single raw unit so that the screen never turns black. Keyboard backlights can always be turned off.
+    fn floor(&self, device: &str) -> u32 {
+        if self.allow_off {
+            return 0;
+        }
+        let min = self.limits.get(device).and_then(|limits| limits.min);
+        min.unwrap_or(u32::from(!self.keyboard))
     }
 }
 
-pub fn parse<'a>(mut args: Skip<Args>) -> Result<Config<'a>, DynError> {
+fn parse_curve(curve: &str) -> Result<BrightnessCurve, BlightError> {
+    match curve.split_once('=') {
+        None => match curve {
+            "linear" => Ok(BrightnessCurve::Linear),
+            "log" | "logarithmic" => Ok(BrightnessCurve::Logarithmic),
+            "gamma" => Ok(BrightnessCurve::Gamma(DEFAULT_GAMMA)),
+            _ => Err(BlightError::InvalidCurve),
+        },
+        Some(("gamma", exp)) => match exp.parse::<f64>() {
+            Ok(exp) if exp.is_finite() && exp > 0.0 => Ok(BrightnessCurve::Gamma(exp)),
+            _ => Err(BlightError::InvalidCurve),
+        },
+        Some(_) => Err(BlightError::InvalidCurve),
+    }
+}
+
+fn parse_duration(duration: &str) -> Result<Duration, BlightError> {
+    let (value, unit) = if let Some(ms) = duration.strip_suffix("ms") {
+        (ms, 1e-3)
+    } else if let Some(s) = duration.strip_suffix('s') {
+        (s, 1.0)
+    } else {
+        (duration, 1e-3)
+    };
+    value
+        .parse::<f64>()
+        .ok()
+        .and_then(|v| Duration::try_from_secs_f64(v * unit).ok())
+        .ok_or(BlightError::InvalidDuration)
+}
+
+fn parse_easing(easing: &str) -> Result<Easing, BlightError> {
+    match easing {
+        "linear" => Ok(Easing::Linear),
+        "ease-in-out" => Ok(Easing::EaseInOut),
+        "ease-out" => Ok(Easing::EaseOut),
+        "perceptual" => Ok(Easing::Perceptual),
+        _ => Err(BlightError::InvalidEasing),
+    }
+}
+
+pub fn parse<'a>(args: Skip<Args>) -> Result<Config<'a>, DynError> {
+    parse_with(args, Defaults::load)
+}
+
+/// Config files are only loaded by the commands that take options, so a broken config doesn't get in the way of help and setup
+fn parse_with<'a>(
+    mut args: impl Iterator<Item = String>,
+    load_defaults: impl FnOnce() -> Result<Defaults, BlightError>,
+) -> Result<Config<'a>, DynError> {
     use BlightError::*;
     use Command::*;
 
-    let option_parser =
-        |args: Skip<Args>| -> Options { args.fold(Options::default(), |op, arg| op.set(arg)) };
-
     let no_op = |cm: Command| (cm, Options::default());
 
     let (command, options) = if let Some(arg) = args.next() {
         match arg.as_str() {
             "setup" => no_op(Setup),
             "help" => no_op(Help),
-            "restore" => no_op(Restore),
-            "list" => no_op(List),
-            "status" => (Status, option_parser(args)),
-            "save" => (Save, option_parser(args)),
-
-            "set" => {
-                let val: u32 = args
-                    .next()
-                    .ok_or(MissingValue)?
-                    .parse()
-                    .or(Err(InvalidValue))?;
-
-                (Set(val), option_parser(args))
-            }
-
-            ch @ ("inc" | "dec") => {
-                let value: u32 = args
-                    .next()
-                    .ok_or(MissingValue)?
-                    .parse()
-                    .map_err(|_| InvalidValue)?;
+            "restore" => (Restore, Options::parse(args, &load_defaults()?)?),
+            "list" => (List, Options::parse(args, &load_defaults()?)?),
+            "get" => (Get, Options::parse(args, &load_defaults()?)?),
+            "monitor" => (Monitor, Options::parse(args, &load_defaults()?)?),
+            "status" => (Status, Options::parse(args, &load_defaults()?)?),
+            "save" => (Save, Options::parse(args, &load_defaults()?)?),
+            ch @ ("set" | "inc" | "dec") => parse_change(ch, args, &load_defaults()?)?,
 
-                let dir = if ch == "inc" { Inc } else { Dec };
-
-                (Adjust { dir, value }, option_parser(args))
+            "kbd" => {
+                let (command, mut options) = match args.next().as_deref() {
+                    Some("list") => no_op(KbdList),
+                    Some(ch @ ("set" | "inc" | "dec")) => {
+                        // the default device is a backlight, which is no use for keyboards
+                        let defaults = Defaults {
+                            device: None,
+                            ..load_defaults()?
+                        };
+                        parse_change(ch, args, &defaults)?
+                    }
+                    Some(_) => Err(UnrecognisedCommand)?,
+                    None => Err(MissingValue)?,
+                };
+                options.keyboard = true;
+                (command, options)
             }
             _ => Err(UnrecognisedCommand)?,
         }
@@ -111,37 +243,278 @@ pub fn parse<'a>(mut args: Skip<Args>) -> Result<Config<'a>, DynError> {
     Ok(Config { command, options })
 }
 
+/// Parses the value and options of the set, inc and dec commands.
+/// The value of inc and dec can be left out if a default step is configured.
+fn parse_change<'a>(
+    command: &str,
+    args: impl Iterator<Item = String>,
+    defaults: &Defaults,
+) -> Result<(Command, Options<'a>), BlightError> {
+    let mut args = args.peekable();
+    // a dash followed by a digit is a signed number, so inc -3 gets to parse_value and is rejected for its sign instead of being skipped as a flag
+    let arg = args.next_if(|arg| match arg.strip_prefix('-') {
+        Some(rest) => {
+            command == "set" || rest.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        }
+        None => true,
+    });
+    let options = Options::parse(args, defaults)?;
+    let arg = match (arg, options.step) {
+        (Some(arg), _) => arg,
+        (None, Some(step)) => format!("{step}%"),
+        (None, None) => return Err(BlightError::MissingValue),
+    };
+    // values without a unit are raw for set and percentages for inc and dec, unless --raw is given
+    let (dir, value) = parse_value(&arg, command == "set" || options.raw)?;
+    let command = match (command, dir) {
+        ("set", None) => Command::Set(value),
+        ("set", Some(dir)) => Command::Adjust { dir, value },
+        (_, Some(_)) => return Err(invalid_value(&arg, 0, ValueError::Sign)),
+        ("inc", None) => Command::Adjust { dir: Inc, value },
+        _ => Command::Adjust { dir: Dec, value },
+    };
+    Ok((command, options))
+}
+
+/// Parses a value of the form `[+|-]<number>[%]`, returning the direction of relative values.
+/// Numbers without `%` are raw values if `raw` is true and percentages otherwise, only percentages can be fractional.
+fn parse_value(token: &str, raw: bool) -> Result<(Option<Direction>, Value), BlightError> {
+    let (dir, rest) = match token.as_bytes().first() {
+        Some(b'+') => (Some(Inc), &token[1..]),
+        Some(b'-') => (Some(Dec), &token[1..]),
+        _ => (None, token),
+    };
+    let start = token.len() - rest.len();
+    let (number, percent) = match rest.strip_suffix('%') {
+        Some(number) => (number, true),
+        None => (rest, !raw),
+    };
+    let invalid = |at, reason| invalid_value(token, start + at, reason);
+    if number.is_empty() {
+        return Err(invalid(0, ValueError::MissingNumber));
+    }
+    let mut seen_dot = false;
+    for (i, c) in number.char_indices() {
+        match c {
+            '0'..='9' => (),
+            '.' if !seen_dot && percent => seen_dot = true,
+            '.' if !percent => return Err(invalid(i, ValueError::Fraction)),
+            c => return Err(invalid(i, ValueError::UnexpectedChar(c))),
+        }
+    }
+    let value = if percent {
+        number
+            .parse()
+            .map(Value::Percent)
+            .map_err(|_| invalid(0, ValueError::MissingNumber))?
+    } else {
+        number
+            .parse()
+            .map(Value::Raw)
+            .map_err(|_| invalid(0, ValueError::TooLarge))?
+    };
+    Ok((dir, value))
+}
+
+/// Points at the character at the given byte offset of the token
+fn invalid_value(token: &str, at: usize, reason: ValueError) -> BlightError {
+    BlightError::InvalidValue {
+        token: token.to_owned(),
+        at: token[..at].chars().count(),
+        reason,
+    }
+}
+
 type SuccessMessage = &'static str;
 
 pub fn execute(conf: Config) -> Result<SuccessMessage, DynError> {
     use Command::*;
 
+    let root = sysfs_root();
     match conf.command {
         Help => print_help(),
         ShortHelp => print_shelp(),
-        List => print_devices(),
+        List => print_devices(&root, conf.options.json)?,
+        KbdList => print_keyboards(&root)?,
+        Monitor => monitor(&root, conf.options.device)?,
         Setup => setup::run(),
-        Status => print_status(conf.options.device)?,
-        Save => save(conf.options.device)?,
-        Restore => restore()?,
+        Status => print_status(
+            &root,
+            conf.options.device,
+            conf.options.all,
+            conf.options.json,
+        )?,
+        Get => get(&root, &conf.options)?,
+        Save => save(&root, conf.options.device)?,
+        Restore => {
+            let _lock = acquire_lock();
+            restore(&root, &conf.options)?
+        }
         Set(v) => {
             let _lock = acquire_lock();
-            blight::set_bl(v, conf.options.device)?
+            set(&root, v, conf.options)?
         }
         Adjust { dir, value } => {
             let _lock = acquire_lock();
-            blight::change_bl(value, conf.options.sweep, dir, conf.options.device)?
+            adjust(&root, value, dir, conf.options)?
         }
     };
 
     Ok(gen_success_msg(&conf.command))
 }
 
+fn sysfs_root() -> PathBuf {
+    env::var_os(ROOT_VAR).map_or_else(|| PathBuf::from(blight::SYSFS), PathBuf::from)
+}
+
+fn set(root: &Path, val: Value, options: Options) -> Result<(), BlibError> {
+    if options.all {
+        // Raw values mean something different for every device, so the value is taken as a percentage
+        let val = match val {
+            Value::Raw(raw) => Value::Percent(f64::from(raw)),
+            percent => percent,
+        };
+        let group = open_group(root, &options)?;
+        let values = group.to_raw(val);
+        return apply_group(&group, &limit_group(&group, values, &options), &options);
+    }
+    if let Some(monitor) = open_monitor(root, &options)? {
+        let value = monitor.floor(options.limit(monitor.name(), monitor.to_raw(val)));
+        return apply(&monitor, value, &options);
+    }
+    let device = open(root, &options)?;
+    let value = device.floor(options.limit(device.name(), device.to_raw(val)));
+    apply(&device, value, &options)
+}
+
+fn adjust(root: &Path, step: Value, dir: Direction, options: Options) -> Result<(), BlibError> {
+    if options.all {
+        let mut group = open_group(root, &options)?;
+        if let Some(curve) = options.curve {
+            for device in group.devices_mut() {
+                device.set_curve(curve);
+            }
+        }
+        let values = group.calculate_change_by(step, dir);
+        return apply_group(&group, &limit_group(&group, values, &options), &options);
+    }
+    if let Some(mut monitor) = open_monitor(root, &options)? {
+        if let Some(curve) = options.curve {
+            monitor.set_curve(curve);
+        }
+        let change = options.limit(monitor.name(), monitor.calculate_change_by(step, dir));
+        return apply(&monitor, change, &options);
+    }
+    let mut device = open(root, &options)?;
+    if let Some(curve) = options.curve {
+        device.set_curve(curve);
+    }
+    let change = options.limit(device.name(), device.calculate_change_by(step, dir));
+    apply(&device, change, &options)
+}
+
+/// Keeps every value within the limits configured for its device and above its floor
+fn limit_group(group: &DeviceGroup, values: Vec<u32>, options: &Options) -> Vec<u32> {
+    group
+        .devices()
+        .iter()
+        .zip(values)
+        .map(|(device, value)| device.floor(options.limit(device.name(), value)))
+        .collect()
+}
+
+/// Opens the device picked through the options, which is a keyboard backlight for the kbd command
+fn open(root: &Path, options: &Options) -> Result<Device, BlibError> {
+    let mut device = if options.keyboard {
+        Device::keyboard_in(root, options.device.clone())
+    } else {
+        Device::new_in(root, options.device.clone())
+    }?;
+    device.set_min(options.floor(device.name()));
+    Ok(device)
+}
+
+/// Opens the external monitor if the device picked through the options is a DDC/CI output (like DP-2) rather than a backlight device
+fn open_monitor(root: &Path, options: &Options) -> Result<Option<Monitor>, BlibError> {
+    let Some(name) = options.device.as_deref().filter(|_| !options.keyboard) else {
+        return Ok(None);
+    };
+    match Monitor::open_in(root, name) {
+        Ok(mut monitor) => {
+            monitor.set_min(options.floor(monitor.name()));
+            Ok(Some(monitor))
+        }
+        Err(BlibError::NoDeviceFound | BlibError::ReadDrmDir(_)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Same as [``open``], for the --all flag
+fn open_group(root: &Path, options: &Options) -> Result<DeviceGroup, BlibError> {
+    let mut group = if options.keyboard {
+        let keyboards = blight::keyboards_in(root)?;
+        if keyboards.is_empty() {
+            return Err(BlibError::NoDeviceFound);
+        }
+        DeviceGroup::new(keyboards)
+    } else {
+        DeviceGroup::all_in(root)?
+    };
+    for device in group.devices_mut() {
+        device.set_min(options.floor(device.name()));
+    }
+    Ok(group)
+}
+
+/// Writes the value with the kind of change picked through the options.
+/// A duration or easing function results in a timed sweep, otherwise the sweep flag decides between a regular and a sweep change.
+fn apply(device: &impl Backlight, value: u32, options: &Options) -> Result<(), BlibError> {
+    if value == device.current() {
+        return Ok(());
+    }
+    if options.duration.is_some() || options.easing.is_some() {
+        return device.sweep_write_for(
+            value,
+            options.duration.unwrap_or(DEFAULT_DURATION),
+            options.easing.unwrap_or_default(),
+        );
+    }
+    match options.sweep {
+        Change::Sweep => device.sweep_write(value, Delay::default()),
+        Change::Regular => device.write_raw(value),
+    }
+}
+
+/// Same as [``apply``], for all devices of the group at once
+fn apply_group(group: &DeviceGroup, values: &[u32], options: &Options) -> Result<(), BlibError> {
+    if options.duration.is_some() || options.easing.is_some() {
+        return group.sweep_write_for(
+            values,
+            options.duration.unwrap_or(DEFAULT_DURATION),
+            options.easing.unwrap_or_default(),
+        );
+    }
+    match options.sweep {
+        Change::Sweep => group.sweep_write(values, Delay::default()),
+        Change::Regular => group.write_values(values),
+    }
+}
+
 #[derive(Debug)]
 pub enum BlightError {
     UnrecognisedCommand,
     MissingValue,
-    InvalidValue,
+    /// A value that doesn't follow the value grammar, `at` is the index of the offending character in the token
+    InvalidValue {
+        token: String,
+        at: usize,
+        reason: ValueError,
+    },
+    InvalidCurve,
+    InvalidDuration,
+    InvalidEasing,
+    InvalidConfig(PathBuf, String),
+    InvalidEnv(&'static str),
     CreateSaveDir(PathBuf),
     WriteToSaveFile(PathBuf),
     ReadFromSave(std::io::Error),
@@ -149,12 +522,55 @@ pub enum BlightError {
     SaveParseErr,
 }
 
+/// Why a value was rejected, see [``BlightError::InvalidValue``]
+#[derive(Debug, PartialEq, Eq)]
+pub enum ValueError {
+    MissingNumber,
+    UnexpectedChar(char),
+    /// Raw values can't be fractional
+    Fraction,
+    TooLarge,
+    /// inc and dec already give the direction
+    Sign,
+}
+
+impl std::fmt::Display for ValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ValueError::*;
+        match self {
+            MissingNumber => write!(f, "expected a number"),
+            UnexpectedChar(c) => write!(f, "unexpected character '{c}'"),
+            Fraction => write!(f, "raw values can't be fractional"),
+            TooLarge => write!(f, "number is too large"),
+            Sign => write!(f, "inc and dec take no sign"),
+        }
+    }
+}
+
 impl Tip for BlightError {
     fn tip(&self) -> Option<Cow<'static, str>> {
         use BlightError::*;
         match self {
             UnrecognisedCommand => Some("try 'blight help' to see all commands".into()),
-            InvalidValue => Some("make sure the value is a valid positive integer".into()),
+            InvalidValue { reason, .. } => Some(match reason {
+                ValueError::Fraction => {
+                    "add % to set a fraction of max brightness, like 0.5%".into()
+                }
+                ValueError::Sign => "use set for relative values, like 'blight set +5%'".into(),
+                _ => "values look like 40 (raw), 40% or 0.5%, and set also takes +5% or -10".into(),
+            }),
+            InvalidCurve => {
+                Some("supported curves are linear, log, gamma and gamma=<exponent>".into())
+            }
+            InvalidDuration => Some("durations look like 300ms, 1.5s or 300 (milliseconds)".into()),
+            InvalidEasing => Some(
+                "supported easing functions are linear, ease-in-out, ease-out and perceptual"
+                    .into(),
+            ),
+            InvalidConfig(..) => Some(
+                "see the Configuration section of the README for the supported settings".into(),
+            ),
+            InvalidEnv(var) => Some(format!("fix or unset {var}").into()),
             NoSaveFound => Some("try using 'blight save' first".into()),
             MissingValue => {
                 Some("try 'blight help' to see all commands and their supported args".into())
@@ -172,7 +588,18 @@ impl std::fmt::Display for BlightError {
         match self {
             UnrecognisedCommand => write!(f, "unrecognised command entered"),
             MissingValue => write!(f, "required argument was not provided for the command"),
-            InvalidValue => write!(f, "invalid value provided"),
+            InvalidValue { token, at, reason } => write!(
+                f,
+                "invalid value provided ({reason})\n  {token}\n  {:>at$}^",
+                ""
+            ),
+            InvalidCurve => write!(f, "invalid brightness curve provided"),
+            InvalidDuration => write!(f, "invalid sweep duration provided"),
+            InvalidEasing => write!(f, "invalid easing function provided"),
+            InvalidConfig(loc, err) => {
+                write!(f, "failed to read config file at {}\n{err}", loc.display())
+            }
+            InvalidEnv(var) => write!(f, "invalid value in environment variable {var}"),
             CreateSaveDir(loc) => write!(f, "failed to create save directory at {}", loc.display()),
             WriteToSaveFile(loc) => write!(f, "failed to write to save file at {}", loc.display()),
             ReadFromSave(err) => write!(f, "failed to read from save file\n{err}"),
@@ -184,6 +611,14 @@ impl std::fmt::Display for BlightError {
 
 impl Error for BlightError {}
 
+/// Turns colors off when stdout isn't a terminal or `NO_COLOR` is set, so that piped output is plain text
+pub fn init_color() {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|var| !var.is_empty());
+    if no_color || !io::stdout().is_terminal() {
+        colored::control::set_override(false);
+    }
+}
+
 pub fn print_err(e: DynError) {
     eprintln!("{} {e}", "Error".red().bold());
     if let Some(tip) = e
@@ -212,60 +647,331 @@ fn gen_success_msg(cm: &Command) -> SuccessMessage {
     }
 }
 
-fn check_write_perm(device_name: &str, bldir: &str) -> Result<(), std::io::Error> {
-    let path = format!("{bldir}/{device_name}/brightness");
-    fs::read_to_string(&path)
-        .and_then(|contents| fs::write(&path, contents))
-        .and(Ok(()))
+pub fn print_status(
+    root: &Path,
+    device_name: Option<Cow<str>>,
+    all: bool,
+    json: bool,
+) -> Result<(), BlibError> {
+    let devices = if all {
+        blight::devices_in(root)?
+            .into_iter()
+            .map(|info| Device::new_in(root, Some(Cow::from(info.name))))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        vec![Device::new_in(root, device_name)?]
+    };
+    if json {
+        let devices = devices.iter().map(json::DeviceStatus::of).collect();
+        json::print(json::Status { devices });
+        return Ok(());
+    }
+    for (i, device) in devices.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        print_device_status(device);
+    }
+    Ok(())
 }
 
-pub fn print_status(device_name: Option<Cow<str>>) -> Result<(), BlibError> {
-    let device = Device::new(device_name)?;
+fn print_device_status(device: &Device) {
+    let write_perm = if device.is_writable() {
+        "Ok".green()
+    } else {
+        "denied".red()
+    };
 
-    let write_perm = match check_write_perm(device.name(), BLDIR) {
-        Ok(_) => "Ok".green(),
-        Err(err) => format!("{err}").red(),
+    let unknown = || "unknown".yellow();
+    let actual = match device.actual_brightness() {
+        Some(actual) if actual == device.current() => actual.to_string().green(),
+        Some(actual) => format!("{actual} (differs from requested value)").red(),
+        None => unknown(),
+    };
+    let power = match device.bl_power() {
+        Some(power @ BlPower::On) => power.to_string().green(),
+        Some(power) => power.to_string().red(),
+        None => unknown(),
     };
+    let kind = device
+        .backlight_type()
+        .map_or_else(unknown, |kind| kind.to_string().green());
+    let gpu = device
+        .gpu()
+        .map_or_else(|| "none".yellow(), |gpu| gpu.to_string().green());
 
     println!(
-        "{}\nDetected device: {}\nWrite permission: {}\nCurrent brightness: {}\nMax brightness: {}",
+        "{}\nDetected device: {}\nWrite permission: {}\nCurrent brightness: {} ({})\nActual brightness: {}\nMax brightness: {}\nType: {}\nGPU: {}\nScale: {}\nPower: {}",
         "Device status".bold(),
         device.name().green(),
         write_perm,
         device.current().to_string().green(),
-        device.max().to_string().green()
+        format!("{:.0}%", device.current_percent()).green(),
+        actual,
+        device.max().to_string().green(),
+        kind,
+        gpu,
+        device.scale().to_string().green(),
+        power,
     );
+}
+
+/// Prints the brightness of the picked device or monitor: a rounded percentage, the raw value with --raw, or a JSON document with --json
+fn get(root: &Path, options: &Options) -> Result<(), BlibError> {
+    fn print(device: &impl Backlight, options: &Options) {
+        if options.json {
+            json::print(json::Get {
+                device: json::Brightness::of(device),
+            });
+        } else if options.raw {
+            println!("{}", device.current());
+        } else {
+            println!("{:.0}%", device.current_percent());
+        }
+    }
+    match open_monitor(root, options)? {
+        Some(monitor) => print(&monitor, options),
+        None => print(&open(root, options)?, options),
+    }
+    Ok(())
+}
+
+/// Prints one line per brightness change until the device can no longer be read
+fn monitor(root: &Path, device_name: Option<Cow<str>>) -> Result<(), BlibError> {
+    let device = Device::new_in(root, device_name)?;
+    for event in device.watch() {
+        let event = event?;
+        println!(
+            "{} {} -> {} ({:.0}%)",
+            event.source_attr,
+            event.old,
+            event.new,
+            device.to_percent(event.new)
+        );
+    }
+    Ok(())
+}
+
+pub fn print_devices(root: &Path, json: bool) -> Result<(), BlibError> {
+    let devices = blight::devices_in(root)?;
+    // external monitors aren't probed here, since every DDC/CI request takes a while
+    let outputs = blight::ddc_outputs_in(root).unwrap_or_default();
+    if json {
+        json::print(json::List {
+            devices: devices.iter().map(json::ListedDevice::from).collect(),
+            outputs: outputs.iter().map(json::ExternalOutput::from).collect(),
+        });
+        return Ok(());
+    }
+    let rows: Vec<[String; 7]> = devices
+        .iter()
+        .map(|info| {
+            [
+                info.name.clone(),
+                info.current.to_string(),
+                info.max.to_string(),
+                format!("{:.0}%", info.percent),
+                info.kind
+                    .map_or_else(|| "unknown".into(), |kind| kind.to_string()),
+                if info.writable { "yes" } else { "no" }.into(),
+                info.connector
+                    .as_ref()
+                    .map_or_else(|| "-".into(), ToString::to_string),
+            ]
+        })
+        .collect();
+    let header = [
+        "DEVICE", "CURRENT", "MAX", "PERCENT", "TYPE", "WRITE", "OUTPUT",
+    ];
+    let widths: Vec<usize> = (0..header.len())
+        .map(|col| {
+            rows.iter()
+                .map(|row| row[col].len())
+                .chain([header[col].len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let line = |cells: [&str; 7]| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_owned()
+    };
+
+    println!("  {}", line(header).bold());
+    for (info, row) in devices.iter().zip(&rows) {
+        let row = line(row.each_ref().map(String::as_str));
+        if info.rank == 0 {
+            println!("{} {}", "*".green().bold(), row.green());
+        } else if info.writable {
+            println!("  {row}");
+        } else {
+            println!("  {}", row.yellow());
+        }
+    }
+
+    match devices.first() {
+        Some(best) if best.rank == 0 => println!(
+            "\n{} {} is picked by auto-detection, because {}",
+            "*".green().bold(),
+            best.name.green(),
+            detection_reason(best, devices.get(1))
+        ),
+        Some(_) => println!(
+            "\n{}",
+            "Auto-detection picks a device whose brightness can't be read, use --device to pick another one".red()
+        ),
+        None => println!("{}", "No backlight devices found".yellow()),
+    }
+
+    if !outputs.is_empty() {
+        println!(
+            "\n{}",
+            "External outputs (DDC/CI, use with --device)".bold()
+        );
+        for output in outputs {
+            println!("  {output}");
+        }
+    }
+    Ok(())
+}
+
+pub fn print_keyboards(root: &Path) -> Result<(), BlibError> {
+    println!("{}", "Keyboard Backlights".bold());
+    for (i, kbd) in blight::keyboards_in(root)?.iter().enumerate() {
+        let line = format!(
+            "{} {}/{} ({:.0}%)",
+            kbd.name(),
+            kbd.current(),
+            kbd.max(),
+            kbd.current_percent()
+        );
+        // The first one is what the kbd command picks without --device
+        if i == 0 {
+            println!("{} {}", "*".green().bold(), line.green());
+        } else {
+            println!("  {line}");
+        }
+    }
     Ok(())
 }
 
-pub fn print_devices() {
-    println!("{}", "Detected Devices".bold());
-    fs::read_dir(BLDIR)
-        .expect("Failed to read Backlight Directory")
-        .for_each(|d| println!("{}", d.unwrap().file_name().to_string_lossy().green()));
+/// Explains why the best device won over the runner-up, following the order in which detection compares devices
+fn detection_reason(best: &DeviceInfo, runner_up: Option<&DeviceInfo>) -> String {
+    let Some(other) = runner_up else {
+        return "it's the only device".into();
+    };
+    let raw = |info: &DeviceInfo| info.kind == Some(BacklightType::Raw);
+    let lit =
+        |info: &DeviceInfo| raw(info) && info.connector.as_ref().is_some_and(Connector::is_active);
+    let active = |info: &DeviceInfo| raw(info) && info.active_gpu;
+    let gpu = |info: &DeviceInfo| info.gpu.as_ref().map(Gpu::kind);
+    let kind = |info: &DeviceInfo| {
+        info.kind
+            .map_or_else(|| "unknown".into(), |kind| kind.to_string())
+    };
+    if best.kind != other.kind {
+        format!(
+            "its {} interface ranks above the {} interface of {}",
+            kind(best),
+            kind(other),
+            other.name
+        )
+    } else if lit(best) != lit(other) {
+        let output = best
+            .connector
+            .as_ref()
+            .map(Connector::output)
+            .unwrap_or_default();
+        format!("it lights up the internal panel ({output})")
+    } else if active(best) != active(other) {
+        let gpu = best
+            .gpu
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        format!("its GPU drives the display ({gpu})")
+    } else if gpu(best) != gpu(other) {
+        match gpu(best) {
+            Some(GpuKind::Integrated) => "it belongs to the integrated GPU".into(),
+            _ => format!("it belongs to a GPU, unlike {}", other.name),
+        }
+    } else {
+        "devices that rank the same are picked by name".into()
+    }
 }
 
 pub fn print_help() {
     let title = "blight: A backlight utility for Linux that plays well with hybrid GPUs";
     let quote = "\"And man said, \'let there b-light\' and there was light.\" - Some Book 1:3";
-    let flags = "Flags: sweep [--sweep, -s], dev [--device <name>, -d <name>]
-    Sweep flag lets you increase brightness gradually, resulting in a smooth change.
-    Dev (short for device) flag lets you specify a backlight device target other than the default one.";
+    let flags = "Flags: sweep [--sweep, -s], dev [--device <name>, -d <name>], output [--output <name>, -o <name>], curve [--curve <name>, -c <name>]
+    Sweep flag lets you increase brightness gradually, resulting in a smooth change (--no-sweep turns a configured sweep off).
+    Fade flags [--duration <time>, --easing <name>] sweep over a fixed duration (e.g. 300ms, 1s),
+    using an easing function: linear, ease-in-out, ease-out or perceptual.
+    Dev (short for device) flag lets you specify a backlight device target other than the default one.
+    Output flag picks the device that lights up a display output instead (e.g. eDP-1), it works wherever the dev flag does.
+    External outputs (e.g. DP-2) control the monitor plugged into them over DDC/CI, for set, inc and dec.
+    All flag [--all, -a] changes every panel at once (one device per panel), with sweeps finishing together (set then takes a percentage).
+    For status, it shows the status of every device.
+    Values are raw for set and percentages for inc and dec (raw with --raw), add % for a percentage (fractions like 0.5% work),
+    and + or - for set to change relative to the current value (e.g. set +5%, set -10).
+    Json flag [--json] prints status, list and get as JSON for scripts, see the README for the schema.
+    Allow-off flag [--allow-off] lets set and dec go below the floor that keeps the screen from turning black
+    (1 raw unit, or the min configured for the device).
+    Curve flag lets you pick how steps are spread: linear, log, gamma or gamma=<exponent> (default follows the device).
+    Defaults for all of these (and the step of inc and dec) can be set in /etc/blight/config.toml,
+    ~/.config/blight/config.toml or BLIGHT_* environment variables, see the README.";
     let commands: String = [
-        ("inc [val] [flags: dev, sweep]", "-> increase brightness"),
-        ("dec [val] [flags: dev, sweep]", "-> decrease brightness"),
-        ("set [val] [flags: dev]", "-> set custom brightness value"),
+        (
+            "inc [val] [flags: dev, sweep, curve, fade, all, raw]",
+            "-> increase brightness",
+        ),
+        (
+            "dec [val] [flags: dev, sweep, curve, fade, all, raw, allow-off]",
+            "-> decrease brightness",
+        ),
+        (
+            "set [val|val%|+val|-val] [flags: dev, sweep, fade, all, allow-off]",
+            "-> set custom brightness value or percentage",
+        ),
         (
             "save [flags: dev]",
             "-> save current brightness value to restore later",
         ),
-        ("restore", "-> restore saved brightness value\n"),
+        (
+            "restore [flags: sweep, fade]",
+            "-> restore saved brightness value\n",
+        ),
         (
             "setup",
             "-> installs udev rules and adds user to video group (run with sudo)",
         ),
-        ("status [flags: dev]", "-> backlight device status"),
-        ("list", "-> list all backlight devices"),
+        (
+            "kbd inc|dec|set [val] [flags: dev, sweep, fade, all, raw]",
+            "-> change keyboard backlight (dev picks an LED by name)",
+        ),
+        ("kbd list", "-> list keyboard backlights"),
+        (
+            "status [flags: dev, all, json]",
+            "-> backlight device status (--all for every device)",
+        ),
+        (
+            "get [flags: dev, raw, json]",
+            "-> print current brightness as a percentage (--raw for the raw value)",
+        ),
+        (
+            "list [flags: json]",
+            "-> list all backlight devices and their panel outputs",
+        ),
+        (
+            "monitor [flags: dev]",
+            "-> print a line whenever brightness changes (for status bars)",
+        ),
         ("help", "-> display help"),
     ]
     .into_iter()
@@ -278,6 +984,7 @@ Examples:
     blight status (show backlight device status info)
     blight inc 5 --sweep (increase brightness smoothly by 5%)
     blight set 10 (sets the brightness value to 10)
+    blight set 50% -d DP-2 (sets the external monitor on DP-2 to 50% over DDC/CI)
     blight inc 2 -s -d nvidia_0 (increases nvidia_0's brightness smoothly by 2%)";
 
     println!(
@@ -309,8 +1016,8 @@ pub fn print_shelp() {
     );
 }
 
-pub fn save(device_name: Option<Cow<str>>) -> Result<(), DynError> {
-    let device = Device::new(device_name)?;
+pub fn save(root: &Path, device_name: Option<Cow<str>>) -> Result<(), DynError> {
+    let device = Device::new_in(root, device_name)?;
     let mut savedir = PathBuf::from(env::var("HOME").unwrap() + SAVEDIR);
 
     if !savedir.exists() && fs::create_dir_all(&savedir).is_err() {
@@ -325,7 +1032,7 @@ pub fn save(device_name: Option<Cow<str>>) -> Result<(), DynError> {
     Ok(())
 }
 
-pub fn restore() -> Result<(), DynError> {
+fn restore(root: &Path, options: &Options) -> Result<(), DynError> {
     let save = PathBuf::from((env::var("HOME").unwrap() + SAVEDIR) + "/blight.save");
 
     let restore = if save.is_file() {
@@ -335,10 +1042,10 @@ pub fn restore() -> Result<(), DynError> {
     };
 
     let (device_name, val) = restore.split_once(' ').unwrap();
-    let device = Device::new(Some(device_name.into()))?;
+    let device = Device::new_in(root, Some(device_name.into()))?;
 
     let value: u32 = val.parse().map_err(|_| BlightError::SaveParseErr)?;
-    device.write_value(value)?;
+    apply(&device, value, options)?;
     Ok(())
 }
 
@@ -350,7 +1057,7 @@ impl PanicReporter {
             std::panic::set_hook(Box::new(Self::report));
         }
     }
-    fn report(info: &std::panic::PanicInfo) {
+    fn report(info: &std::panic::PanicHookInfo) {
         let tip = "This is unexpected behavior. Please report this issue at https://github.com/VoltaireNoir/blight/issues";
         let payload = info.payload();
         let cause = if let Some(pay) = payload.downcast_ref::<&str>() {
@@ -373,16 +1080,219 @@ fn acquire_lock() -> File {
     let file = OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(false)
         .open(LOCKFILE)
         .expect("failed to open lock file");
     if file.try_lock_exclusive().is_ok() {
         return file;
     }
     println!(
-        "{} {}",
-        "Status".magenta().bold(),
-        "Waiting for another instance to finish"
+        "{} Waiting for another instance to finish",
+        "Status".magenta().bold()
     );
     file.lock_exclusive().expect("failed to acquire lock");
     file
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::Fixture;
+
+    #[test]
+    fn value_grammar() {
+        let parse = |token| parse_value(token, true).unwrap();
+        assert_eq!(parse("40"), (None, Value::Raw(40)));
+        assert_eq!(parse("40%"), (None, Value::Percent(40.0)));
+        assert_eq!(parse("+5%"), (Some(Inc), Value::Percent(5.0)));
+        assert_eq!(parse("-10"), (Some(Dec), Value::Raw(10)));
+        assert_eq!(parse("0.5%"), (None, Value::Percent(0.5)));
+        assert_eq!(
+            parse_value("3", false).unwrap(),
+            (None, Value::Percent(3.0))
+        );
+
+        let err = |token, raw| match parse_value(token, raw) {
+            Err(BlightError::InvalidValue { at, reason, .. }) => (at, reason),
+            other => panic!("{token} parsed as {other:?}"),
+        };
+        assert_eq!(err("4x%", true), (1, ValueError::UnexpectedChar('x')));
+        assert_eq!(err("+%", true), (1, ValueError::MissingNumber));
+        assert_eq!(err("-1.5", true), (2, ValueError::Fraction));
+        assert_eq!(err("1.5.", false), (3, ValueError::UnexpectedChar('.')));
+        assert_eq!(err("99999999999", true), (0, ValueError::TooLarge));
+
+        let change = |command, args: &[&str]| {
+            let args = args.iter().map(ToString::to_string);
+            parse_change(command, args, &Defaults::default())
+        };
+        assert!(matches!(
+            change("set", &["-10", "-s"]),
+            Ok((
+                Command::Adjust {
+                    dir: Dec,
+                    value: Value::Raw(10)
+                },
+                _
+            ))
+        ));
+        assert!(matches!(
+            change("inc", &["3", "--raw"]),
+            Ok((
+                Command::Adjust {
+                    dir: Inc,
+                    value: Value::Raw(3)
+                },
+                _
+            ))
+        ));
+        assert!(matches!(
+            change("inc", &["+3"]),
+            Err(BlightError::InvalidValue {
+                reason: ValueError::Sign,
+                ..
+            })
+        ));
+        let step = Defaults {
+            step: Some(5),
+            ..Defaults::default()
+        };
+        for command in ["inc", "dec"] {
+            let args = ["-3", "-s"].map(String::from).into_iter();
+            assert!(matches!(
+                parse_change(command, args, &step),
+                Err(BlightError::InvalidValue {
+                    at: 0,
+                    reason: ValueError::Sign,
+                    ..
+                })
+            ));
+        }
+        assert!(matches!(
+            parse_change("dec", ["-s".to_owned()].into_iter(), &step),
+            Ok((
+                Command::Adjust {
+                    dir: Dec,
+                    value: Value::Percent(_)
+                },
+                _
+            ))
+        ));
+    }
+
+    #[test]
+    fn broken_config() {
+        let parse = |args: &[&str]| {
+            let args = args.iter().map(ToString::to_string);
+            parse_with(args, || {
+                Err(BlightError::InvalidConfig(
+                    PathBuf::from("config.toml"),
+                    "expected `=`".into(),
+                ))
+            })
+        };
+        assert!(matches!(
+            parse(&["help"]),
+            Ok(Config {
+                command: Command::Help,
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse(&["setup"]),
+            Ok(Config {
+                command: Command::Setup,
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse(&[]),
+            Ok(Config {
+                command: Command::ShortHelp,
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse(&["kbd", "list"]),
+            Ok(Config {
+                command: Command::KbdList,
+                ..
+            })
+        ));
+        assert!(parse(&["set", "50"]).is_err());
+        assert!(parse(&["kbd", "inc"]).is_err());
+    }
+
+    #[test]
+    fn explaining_the_pick() {
+        use std::os::unix::fs::symlink;
+
+        let root = Fixture::new("reason");
+        let add = |name: &str, gpu: &str, vendor: &str, target: &str| {
+            let gpu = root.join("devices/pci0000:00").join(gpu);
+            let target = gpu.join(target);
+            fs::create_dir_all(&target).unwrap();
+            fs::write(gpu.join("vendor"), vendor).unwrap();
+            fs::write(gpu.join("class"), "0x030000").unwrap();
+            let path = root.device("class/backlight", name, 50, 100);
+            fs::write(path.join("type"), "raw").unwrap();
+            symlink(fs::canonicalize(target).unwrap(), path.join("device")).unwrap();
+        };
+        add(
+            "intel_backlight",
+            "0000:00:02.0",
+            "0x8086",
+            "drm/card0/card0-eDP-1",
+        );
+        add("nvidia_0", "0000:00:01.0/0000:01:00.0", "0x10de", "");
+        let edp = root.join("devices/pci0000:00/0000:00:02.0/drm/card0/card0-eDP-1");
+        fs::write(edp.join("status"), "connected").unwrap();
+        fs::write(edp.join("enabled"), "enabled").unwrap();
+        let devices = blight::devices_in(&root).unwrap();
+        let (igpu, dgpu) = (&devices[0], &devices[1]);
+        assert_eq!(
+            (igpu.name.as_str(), dgpu.name.as_str()),
+            ("intel_backlight", "nvidia_0")
+        );
+
+        // every branch follows the order in which detection compares devices
+        let with = |info: &DeviceInfo, change: fn(&mut DeviceInfo)| {
+            let mut info = info.clone();
+            change(&mut info);
+            info
+        };
+        let reason = |best: &DeviceInfo, other: &DeviceInfo| detection_reason(best, Some(other));
+        let firmware = with(igpu, |info| info.kind = Some(BacklightType::Firmware));
+        assert_eq!(
+            reason(&firmware, dgpu),
+            "its firmware interface ranks above the raw interface of nvidia_0"
+        );
+        assert_eq!(
+            reason(igpu, dgpu),
+            "it lights up the internal panel (eDP-1)"
+        );
+        let unlit = with(igpu, |info| info.connector = None);
+        let active = with(dgpu, |info| info.active_gpu = true);
+        assert_eq!(
+            reason(&active, &unlit),
+            "its GPU drives the display (discrete NVIDIA (0000:01:00.0))"
+        );
+        assert_eq!(reason(&unlit, dgpu), "it belongs to the integrated GPU");
+        let no_gpu = with(&unlit, |info| info.gpu = None);
+        assert_eq!(
+            reason(dgpu, &no_gpu),
+            "it belongs to a GPU, unlike intel_backlight"
+        );
+        assert_eq!(
+            reason(&unlit, &unlit),
+            "devices that rank the same are picked by name"
+        );
+        // connectors and active GPUs only count for raw devices
+        let active_firmware = with(&firmware, |info| info.active_gpu = true);
+        assert_eq!(
+            reason(&active_firmware, &firmware),
+            "devices that rank the same are picked by name"
+        );
+        assert_eq!(detection_reason(igpu, None), "it's the only device");
+    }
+}
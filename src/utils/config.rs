@@ -0,0 +1,171 @@
+//! Layered defaults for the CLI options. The system config is read first, then the user config and then environment variables,
+//! each layer overriding the fields set by the previous ones. Flags given on the command line override all of them.
+
+use super::BlightError;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+const SYSTEM_CONFIG: &str = "/etc/blight/config.toml";
+/// Location of the user config, relative to `$XDG_CONFIG_HOME` (or `~/.config`)
+const USER_CONFIG: &str = "blight/config.toml";
+
+/// Defaults for the CLI options, as read from config files and environment variables
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Defaults {
+    pub device: Option<String>,
+    /// Step used by inc and dec when no value is given
+    pub step: Option<u32>,
+    pub sweep: Option<bool>,
+    pub duration: Option<String>,
+    pub easing: Option<String>,
+    pub curve: Option<String>,
+    /// Per-device limits, keyed by device name
+    pub devices: BTreeMap<String, Limits>,
+}
+
+/// Raw values a device is kept within, whatever value is asked for
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Floor of the device, which --allow-off goes below
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+}
+
+impl Defaults {
+    /// Reads all layers. Missing config files are skipped, unreadable or invalid ones are an error.
+    pub fn load() -> Result<Self, BlightError> {
+        let mut defaults = Self::default();
+        for path in [Some(PathBuf::from(SYSTEM_CONFIG)), user_config()]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(layer) = Self::read(&path)? {
+                defaults.merge(layer);
+            }
+        }
+        defaults.merge(Self::from_env(|var| env::var(var).ok())?);
+        Ok(defaults)
+    }
+
+    fn read(path: &Path) -> Result<Option<Self>, BlightError> {
+        let invalid = |err: String| BlightError::InvalidConfig(path.to_owned(), err);
+        match fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map(Some)
+                .map_err(|err| invalid(err.to_string())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(invalid(err.to_string())),
+        }
+    }
+
+    /// Reads the `BLIGHT_*` variables through the given lookup function
+    fn from_env(var: impl Fn(&str) -> Option<String>) -> Result<Self, BlightError> {
+        let step = var("BLIGHT_STEP")
+            .map(|step| step.parse().or(Err(BlightError::InvalidEnv("BLIGHT_STEP"))))
+            .transpose()?;
+        let sweep = var("BLIGHT_SWEEP")
+            .map(|sweep| match sweep.as_str() {
+                "1" | "true" | "yes" => Ok(true),
+                "0" | "false" | "no" => Ok(false),
+                _ => Err(BlightError::InvalidEnv("BLIGHT_SWEEP")),
+            })
+            .transpose()?;
+        Ok(Self {
+            device: var("BLIGHT_DEVICE"),
+            step,
+            sweep,
+            duration: var("BLIGHT_DURATION"),
+            easing: var("BLIGHT_EASING"),
+            curve: var("BLIGHT_CURVE"),
+            devices: BTreeMap::new(),
+        })
+    }
+
+    /// Overrides every field that is set in the other layer, device limits are merged per device
+    fn merge(&mut self, other: Self) {
+        macro_rules! take {
+            ($($field:ident),*) => {
+                $(if other.$field.is_some() {
+                    self.$field = other.$field;
+                })*
+            };
+        }
+        take!(device, step, sweep, duration, easing, curve);
+        for (name, limits) in other.devices {
+            let entry = self.devices.entry(name).or_default();
+            entry.min = limits.min.or(entry.min);
+            entry.max = limits.max.or(entry.max);
+        }
+    }
+}
+
+fn user_config() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join(USER_CONFIG))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layering() {
+        let mut defaults: Defaults = toml::from_str(
+            r#"
+            device = "intel_backlight"
+            step = 5
+            sweep = true
+            [devices.intel_backlight]
+            min = 10
+            max = 900
+            "#,
+        )
+        .unwrap();
+        let user: Defaults = toml::from_str(
+            r#"
+            step = 2
+            curve = "log"
+            [devices.intel_backlight]
+            min = 20
+            "#,
+        )
+        .unwrap();
+        defaults.merge(user);
+        defaults.merge(
+            Defaults::from_env(|var| match var {
+                "BLIGHT_SWEEP" => Some("0".into()),
+                "BLIGHT_DURATION" => Some("200ms".into()),
+                _ => None,
+            })
+            .unwrap(),
+        );
+
+        assert_eq!(defaults.device.as_deref(), Some("intel_backlight"));
+        assert_eq!(defaults.step, Some(2));
+        assert_eq!(defaults.sweep, Some(false));
+        assert_eq!(defaults.curve.as_deref(), Some("log"));
+        assert_eq!(defaults.duration.as_deref(), Some("200ms"));
+        assert_eq!(
+            defaults.devices["intel_backlight"],
+            Limits {
+                min: Some(20),
+                max: Some(900)
+            }
+        );
+
+        assert!(toml::from_str::<Defaults>("stepp = 5").is_err());
+        assert!(matches!(
+            Defaults::from_env(|var| (var == "BLIGHT_STEP").then(|| "five".into())),
+            Err(BlightError::InvalidEnv("BLIGHT_STEP"))
+        ));
+    }
+}
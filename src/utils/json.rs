@@ -0,0 +1,255 @@
+//! JSON output of the status, list and get commands (--json), for scripts and status bars.
+//!
+//! Every document is an object with a `version` field holding [``VERSION``], which is bumped whenever a field is removed or changes meaning.
+//! Fields can be added without bumping it, so consumers should ignore the ones they don't know. The schema is documented in the README.
+
+use blight::{Backlight, BlPower, Connector, DdcOutput, Device, DeviceInfo, Gpu};
+use serde::Serialize;
+use std::borrow::Cow;
+
+/// Version of the schema described in the README
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Document<T> {
+    version: u32,
+    #[serde(flatten)]
+    body: T,
+}
+
+/// Prints the body as a single line document, tagged with the schema version
+pub fn print(body: impl Serialize) {
+    let doc = Document {
+        version: VERSION,
+        body,
+    };
+    // the documents only hold strings and numbers, paths are converted lossily beforehand, so serializing can't fail
+    println!(
+        "{}",
+        serde_json::to_string(&doc).expect("failed to serialize output")
+    );
+}
+
+/// Fields shared by every device in every document
+#[derive(Serialize)]
+pub struct Brightness<'a> {
+    name: &'a str,
+    current: u32,
+    max: u32,
+    /// Current brightness as a percentage of max, not rounded
+    percent: f64,
+}
+
+impl<'a> Brightness<'a> {
+    pub fn of(device: &'a impl Backlight) -> Self {
+        Self {
+            name: device.name(),
+            current: device.current(),
+            max: device.max(),
+            percent: device.current_percent(),
+        }
+    }
+}
+
+/// Body of `get --json`
+#[derive(Serialize)]
+pub struct Get<'a> {
+    pub device: Brightness<'a>,
+}
+
+/// Body of `status --json`, with a single device unless --all is given
+#[derive(Serialize)]
+pub struct Status<'a> {
+    pub devices: Vec<DeviceStatus<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct DeviceStatus<'a> {
+    #[serde(flatten)]
+    brightness: Brightness<'a>,
+    path: Cow<'a, str>,
+    writable: bool,
+    /// Value the hardware applied, which can differ from current
+    actual: Option<u32>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    gpu: Option<GpuInfo>,
+    scale: String,
+    power: Option<Power>,
+}
+
+impl<'a> DeviceStatus<'a> {
+    pub fn of(device: &'a Device) -> Self {
+        Self {
+            brightness: Brightness::of(device),
+            path: device.device_path().to_string_lossy(),
+            writable: device.is_writable(),
+            actual: device.actual_brightness(),
+            kind: device.backlight_type().map(|kind| kind.to_string()),
+            gpu: device.gpu().as_ref().map(GpuInfo::from),
+            scale: device.scale().to_string(),
+            power: device.bl_power().map(Power::from),
+        }
+    }
+}
+
+/// Body of `list --json`
+#[derive(Serialize)]
+pub struct List<'a> {
+    pub devices: Vec<ListedDevice<'a>>,
+    pub outputs: Vec<ExternalOutput<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct ListedDevice<'a> {
+    #[serde(flatten)]
+    brightness: Brightness<'a>,
+    path: Cow<'a, str>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    writable: bool,
+    output: Option<Output<'a>>,
+    gpu: Option<GpuInfo>,
+    /// Whether the GPU is the one driving the display
+    active_gpu: bool,
+    /// Position in the detection order
+    rank: usize,
+    /// Whether auto-detection picks this device
+    picked: bool,
+}
+
+impl<'a> From<&'a DeviceInfo> for ListedDevice<'a> {
+    fn from(info: &'a DeviceInfo) -> Self {
+        Self {
+            brightness: Brightness {
+                name: &info.name,
+                current: info.current,
+                max: info.max,
+                percent: info.percent,
+            },
+            path: info.path.to_string_lossy(),
+            kind: info.kind.map(|kind| kind.to_string()),
+            writable: info.writable,
+            output: info.connector.as_ref().map(Output::from),
+            gpu: info.gpu.as_ref().map(GpuInfo::from),
+            active_gpu: info.active_gpu,
+            rank: info.rank,
+            picked: info.rank == 0,
+        }
+    }
+}
+
+/// The display output a device lights up
+#[derive(Serialize)]
+struct Output<'a> {
+    name: &'a str,
+    connected: bool,
+    enabled: bool,
+}
+
+impl<'a> From<&'a Connector> for Output<'a> {
+    fn from(connector: &'a Connector) -> Self {
+        Self {
+            name: connector.output(),
+            connected: connector.is_connected(),
+            enabled: connector.is_enabled(),
+        }
+    }
+}
+
+/// The GPU a device belongs to
+#[derive(Serialize)]
+struct GpuInfo {
+    vendor: String,
+    slot: String,
+    /// Either integrated or discrete
+    kind: String,
+}
+
+impl From<&Gpu> for GpuInfo {
+    fn from(gpu: &Gpu) -> Self {
+        Self {
+            vendor: gpu.vendor().to_string(),
+            slot: gpu.slot().to_owned(),
+            kind: gpu.kind().to_string(),
+        }
+    }
+}
+
+/// The power state of a backlight, level being the raw blanking level (0 when on)
+#[derive(Serialize)]
+struct Power {
+    on: bool,
+    level: u32,
+}
+
+impl From<BlPower> for Power {
+    fn from(power: BlPower) -> Self {
+        match power {
+            BlPower::On => Self { on: true, level: 0 },
+            BlPower::Off(level) => Self { on: false, level },
+        }
+    }
+}
+
+/// An external output that can be controlled over DDC/CI
+#[derive(Serialize)]
+pub struct ExternalOutput<'a> {
+    name: &'a str,
+    connector: &'a str,
+    bus: Cow<'a, str>,
+}
+
+impl<'a> From<&'a DdcOutput> for ExternalOutput<'a> {
+    fn from(output: &'a DdcOutput) -> Self {
+        Self {
+            name: output.output(),
+            connector: output.connector(),
+            bus: output.bus().to_string_lossy(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema() {
+        let doc = Document {
+            version: VERSION,
+            body: Get {
+                device: Brightness {
+                    name: "intel_backlight",
+                    current: 40,
+                    max: 100,
+                    percent: 40.0,
+                },
+            },
+        };
+        assert_eq!(
+            serde_json::to_string(&doc).unwrap(),
+            r#"{"version":1,"device":{"name":"intel_backlight","current":40,"max":100,"percent":40.0}}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Power::from(BlPower::Off(4))).unwrap(),
+            r#"{"on":false,"level":4}"#
+        );
+    }
+
+    #[test]
+    fn non_utf8_path() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt, path::Path};
+
+        let bus = Path::new(OsStr::from_bytes(b"/tmp/\xffroot/dev/i2c-4"));
+        let output = ExternalOutput {
+            name: "DP-1",
+            connector: "card0-DP-1",
+            bus: bus.to_string_lossy(),
+        };
+        assert_eq!(
+            serde_json::to_string(&output).unwrap(),
+            "{\"name\":\"DP-1\",\"connector\":\"card0-DP-1\",\"bus\":\"/tmp/\u{fffd}root/dev/i2c-4\"}"
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! Watching for brightness changes, no matter who makes them. See [``Device::watch``][crate::Device::watch]
+
+use crate::{attr, err::BlibError, BlResult, Device};
+use rustix::{
+    event::{poll, PollFd, PollFlags},
+    fd::OwnedFd,
+    fs::inotify::{self, CreateFlags, WatchFlags},
+    io::{self, Errno},
+};
+use std::{
+    collections::VecDeque,
+    fmt,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+/// Interval used by [``Device::watch``] to re-read the brightness attributes when no file system event arrives
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+const READ_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// The sysfs attribute a [``BrightnessEvent``] was observed on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BrightnessAttr {
+    /// The `brightness` file, which holds the last requested value
+    Brightness,
+    /// The `actual_brightness` file, which holds the value the hardware actually applied
+    ActualBrightness,
+}
+
+impl BrightnessAttr {
+    /// Returns the name of the attribute file
+    #[must_use]
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Brightness => "brightness",
+            Self::ActualBrightness => "actual_brightness",
+        }
+    }
+}
+
+impl fmt::Display for BrightnessAttr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.file_name())
+    }
+}
+
+/// A change of brightness observed by a [``Watcher``]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BrightnessEvent {
+    pub old: u32,
+    pub new: u32,
+    pub source_attr: BrightnessAttr,
+}
+
+/// A blocking iterator over brightness changes of a device, created with [``Device::watch``].
+///
+/// Changes are picked up through inotify events on the `brightness` and `actual_brightness` files. The kernel does not emit such events for changes
+/// it makes on its own (like firmware handled hotkeys), so the attributes are also re-read whenever the poll interval passes without an event.
+/// If inotify is unavailable, the watcher falls back to polling alone.
+///
+/// The iterator yields an error and ends if the attributes can no longer be read, for example when the device disappears.
+#[derive(Debug)]
+pub struct Watcher {
+    path: PathBuf,
+    inotify: Option<OwnedFd>,
+    interval: Duration,
+    brightness: u32,
+    actual: Option<u32>,
+    pending: VecDeque<BrightnessEvent>,
+    done: bool,
+}
+
+impl Device {
+    /// Starts watching the device for brightness changes made by anyone, see [``Watcher``].
+    /// # Example
+    /// ```ignore
+    /// for event in Device::new(None)?.watch() {
+    ///     let event = event?;
+    ///     println!("{} changed from {} to {}", event.source_attr, event.old, event.new);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn watch(&self) -> Watcher {
+        self.watch_every(DEFAULT_POLL_INTERVAL)
+    }
+
+    /// Same as [``Device::watch``], but with a custom poll interval
+    #[must_use]
+    pub fn watch_every(&self, interval: Duration) -> Watcher {
+        let brightness = read_attr(&self.path, BrightnessAttr::Brightness).unwrap_or(self.current);
+        Watcher {
+            inotify: init_inotify(&self.path),
+            actual: read_attr(&self.path, BrightnessAttr::ActualBrightness),
+            path: self.path.clone(),
+            interval,
+            brightness,
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+}
+
+impl Watcher {
+    /// Returns true if changes are picked up through inotify, false if the watcher only polls
+    #[must_use]
+    pub fn uses_inotify(&self) -> bool {
+        self.inotify.is_some()
+    }
+
+    fn wait(&self) {
+        let Some(fd) = &self.inotify else {
+            thread::sleep(self.interval);
+            return;
+        };
+        let timeout = i32::try_from(self.interval.as_millis()).unwrap_or(i32::MAX);
+        let mut fds = [PollFd::new(fd, PollFlags::IN)];
+        match poll(&mut fds, timeout) {
+            Ok(n) if n > 0 => drain(fd),
+            Ok(_) | Err(Errno::INTR) => (),
+            Err(_) => thread::sleep(self.interval),
+        }
+    }
+
+    /// Reads the attribute, giving a writer that is halfway through replacing the contents of the file a moment to finish
+    fn read(&self, attr: BrightnessAttr) -> BlResult<u32> {
+        for _ in 0..READ_ATTEMPTS {
+            if let Some(value) = read_attr(&self.path, attr) {
+                return Ok(value);
+            }
+            thread::sleep(RETRY_DELAY);
+        }
+        Err(BlibError::ReadCurrent)
+    }
+
+    fn check(&mut self) -> BlResult<()> {
+        let brightness = self.read(BrightnessAttr::Brightness)?;
+        if brightness != self.brightness {
+            self.pending.push_back(BrightnessEvent {
+                old: self.brightness,
+                new: brightness,
+                source_attr: BrightnessAttr::Brightness,
+            });
+            self.brightness = brightness;
+        }
+        if let Some(old) = self.actual {
+            let actual = self.read(BrightnessAttr::ActualBrightness)?;
+            if actual != old {
+                self.pending.push_back(BrightnessEvent {
+                    old,
+                    new: actual,
+                    source_attr: BrightnessAttr::ActualBrightness,
+                });
+                self.actual = Some(actual);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Watcher {
+    type Item = BlResult<BrightnessEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            if self.done {
+                return None;
+            }
+            self.wait();
+            if let Err(err) = self.check() {
+                self.done = true;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+fn read_attr(device_dir: &Path, attr: BrightnessAttr) -> Option<u32> {
+    attr::read_attr(device_dir, attr.file_name())?.parse().ok()
+}
+
+fn init_inotify(device_dir: &Path) -> Option<OwnedFd> {
+    let fd = inotify::init(CreateFlags::NONBLOCK | CreateFlags::CLOEXEC).ok()?;
+    let flags = WatchFlags::MODIFY | WatchFlags::CLOSE_WRITE | WatchFlags::ATTRIB;
+    inotify::add_watch(&fd, device_dir.join("brightness"), flags).ok()?;
+    // actual_brightness is optional, the brightness watch alone is enough to go on
+    let _ = inotify::add_watch(&fd, device_dir.join("actual_brightness"), flags);
+    Some(fd)
+}
+
+/// Reads and discards all queued inotify events, only the fact that something happened matters
+fn drain(fd: &OwnedFd) {
+    let mut buf = [0u8; 1024];
+    while matches!(io::read(fd, &mut buf), Ok(n) if n > 0) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixture::Fixture, BrightnessCurve};
+    use std::fs;
+
+    #[test]
+    fn watching_changes() {
+        let root = Fixture::new("watch");
+        let path = root.join("intel_backlight");
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("brightness"), "10").unwrap();
+        fs::write(path.join("actual_brightness"), "10").unwrap();
+        let d = Device {
+            name: "intel_backlight".into(),
+            current: 10,
+            max: 100,
+            path: path.clone(),
+            curve: BrightnessCurve::Linear,
+            min: 0,
+            #[cfg(feature = "logind")]
+            session: None,
+        };
+
+        for mut watcher in [d.watch(), d.watch_every(Duration::from_millis(20))] {
+            let writer = {
+                let path = path.clone();
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(50));
+                    fs::write(path.join("brightness"), "42").unwrap();
+                    fs::write(path.join("actual_brightness"), "40").unwrap();
+                })
+            };
+            let events: Vec<_> = watcher.by_ref().take(2).map(Result::unwrap).collect();
+            writer.join().unwrap();
+            assert_eq!(
+                events,
+                [
+                    BrightnessEvent {
+                        old: 10,
+                        new: 42,
+                        source_attr: BrightnessAttr::Brightness
+                    },
+                    BrightnessEvent {
+                        old: 10,
+                        new: 40,
+                        source_attr: BrightnessAttr::ActualBrightness
+                    }
+                ]
+            );
+            fs::write(path.join("brightness"), "10").unwrap();
+            fs::write(path.join("actual_brightness"), "10").unwrap();
+        }
+
+        let mut watcher = d.watch_every(Duration::from_millis(20));
+        fs::remove_dir_all(&path).unwrap();
+        assert!(matches!(watcher.next(), Some(Err(BlibError::ReadCurrent))));
+        assert!(watcher.next().is_none());
+    }
+}